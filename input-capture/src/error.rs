@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{EdgeSpan, Position};
+
 #[derive(Debug, Error)]
 pub enum InputCaptureError {
     #[error("error creating input-capture: `{0}`")]
@@ -31,6 +33,15 @@ pub enum CaptureError {
     EndOfStream,
     #[error("io error: `{0}`")]
     Io(#[from] std::io::Error),
+    #[error("edge span {span:?} @ {pos} conflicts with the span {existing:?} of another client")]
+    SpanConflict {
+        pos: Position,
+        span: EdgeSpan,
+        existing: EdgeSpan,
+    },
+    #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
+    #[error("failed to create shm buffer: `{0}` (check that TMPDIR is writable)")]
+    Shm(io::Error),
     #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
     #[error("libei error: `{0}`")]
     Reis(#[from] reis::Error),
@@ -132,6 +143,12 @@ pub enum LayerShellCaptureCreationError {
     Dispatch(#[from] DispatchError),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("no input seat found; cannot capture input")]
+    NoSeat,
+    #[error(
+        "compositor offers no supported shm buffer format (argb8888, abgr8888, xrgb8888, xbgr8888)"
+    )]
+    NoShmFormat,
 }
 
 #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
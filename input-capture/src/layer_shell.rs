@@ -3,12 +3,14 @@ use futures_core::Stream;
 use std::{
     collections::VecDeque,
     env,
+    future::Future,
     io::{self, ErrorKind},
     os::fd::{AsFd, RawFd},
     pin::Pin,
     task::{ready, Context, Poll},
+    time::{Duration, Instant},
 };
-use tokio::io::unix::AsyncFd;
+use tokio::{io::unix::AsyncFd, time::Sleep};
 
 use std::{
     fs::File,
@@ -24,7 +26,7 @@ use wayland_protocols::{
             zwp_keyboard_shortcuts_inhibitor_v1::ZwpKeyboardShortcutsInhibitorV1,
         },
         pointer_constraints::zv1::client::{
-            zwp_locked_pointer_v1::ZwpLockedPointerV1,
+            zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
             zwp_pointer_constraints_v1::{Lifetime, ZwpPointerConstraintsV1},
         },
         relative_pointer::zv1::client::{
@@ -38,9 +40,15 @@ use wayland_protocols::{
     },
 };
 
-use wayland_protocols_wlr::layer_shell::v1::client::{
-    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
-    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+use wayland_protocols_wlr::{
+    foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+    },
 };
 
 use wayland_client::{
@@ -55,7 +63,7 @@ use wayland_client::{
         wl_region, wl_registry, wl_seat, wl_shm, wl_shm_pool,
         wl_surface::WlSurface,
     },
-    Connection, Dispatch, DispatchError, EventQueue, QueueHandle, WEnum,
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle, WEnum,
 };
 
 use input_event::{Event, KeyboardEvent, PointerEvent};
@@ -64,7 +72,7 @@ use crate::{CaptureError, CaptureEvent};
 
 use super::{
     error::{LayerShellCaptureCreationError, WaylandBindError},
-    Capture, Position,
+    Capture, CornerHandoff, EdgeSpan, LockLost, Position,
 };
 
 struct Globals {
@@ -75,10 +83,26 @@ struct Globals {
     seat: wl_seat::WlSeat,
     shm: wl_shm::WlShm,
     layer_shell: ZwlrLayerShellV1,
-    outputs: Vec<WlOutput>,
+    /// outputs and their registry names
+    outputs: Vec<(u32, WlOutput)>,
     xdg_output_manager: ZxdgOutputManagerV1,
+    /// reports the state of toplevel windows, used to detect fullscreen windows
+    foreign_toplevel_manager: Option<ZwlrForeignToplevelManagerV1>,
 }
 
+/// time after which the last position of the pointer no longer tells its direction
+const MOTION_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// globals input capture depends on, capture is disabled while one is removed
+const REQUIRED_GLOBALS: [&str; 6] = [
+    "wl_compositor",
+    "wl_shm",
+    "zwlr_layer_shell_v1",
+    "wl_seat",
+    "zwp_pointer_constraints_v1",
+    "zwp_relative_pointer_manager_v1",
+];
+
 #[derive(Debug, Clone)]
 struct OutputInfo {
     name: String,
@@ -104,6 +128,10 @@ struct State {
     shortcut_inhibitor: Option<ZwpKeyboardShortcutsInhibitorV1>,
     active_windows: Vec<Arc<Window>>,
     focused: Option<Arc<Window>>,
+    /// surface the unlocked pointer is on and its position (surface local)
+    pointer_position: Option<(WlSurface, (f64, f64))>,
+    /// last position of the unlocked pointer (output local), to tell its direction
+    last_position: Option<(WlOutput, (f64, f64), Instant)>,
     g: Globals,
     wayland_fd: RawFd,
     read_guard: Option<ReadEventsGuard>,
@@ -111,6 +139,39 @@ struct State {
     pending_events: VecDeque<(Position, CaptureEvent)>,
     output_info: Vec<(WlOutput, OutputInfo)>,
     scroll_discrete_pending: bool,
+    /// buffer formats supported by the compositor
+    shm_formats: Vec<wl_shm::Format>,
+    /// time the pointer has to rest on a window before it is grabbed
+    arming_delay: Duration,
+    arming: Option<Arming>,
+    /// what happens when the compositor breaks the pointer lock
+    lock_lost: LockLost,
+    /// client the cursor is handed off to in a corner
+    corner_handoff: CornerHandoff,
+    /// pointer waiting to leave an ignored corner
+    corner_pending: Option<(WlSurface, WlPointer, u32)>,
+    /// registry names of the bound required globals
+    global_names: Vec<(u32, &'static str)>,
+    /// required globals removed by the compositor
+    missing_globals: Vec<&'static str>,
+    toplevels: Vec<Toplevel>,
+}
+
+/// toplevel window reported by the foreign toplevel manager
+struct Toplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    /// state received since the last `done` event
+    pending_fullscreen: bool,
+    /// whether the window is focused and fullscreen
+    fullscreen: bool,
+}
+
+/// pointer waiting for the arming delay to pass
+struct Arming {
+    surface: WlSurface,
+    pointer: WlPointer,
+    serial: u32,
+    timer: Pin<Box<Sleep>>,
 }
 
 struct Inner {
@@ -130,7 +191,12 @@ struct Window {
     buffer: wl_buffer::WlBuffer,
     surface: WlSurface,
     layer_surface: ZwlrLayerSurfaceV1,
+    output: WlOutput,
     pos: Position,
+    span: EdgeSpan,
+    section: EdgeSection,
+    /// size of the output
+    size: (i32, i32),
 }
 
 impl Window {
@@ -139,17 +205,27 @@ impl Window {
         qh: &QueueHandle<State>,
         output: &WlOutput,
         pos: Position,
+        span: EdgeSpan,
         size: (i32, i32),
-    ) -> Window {
-        log::debug!("creating window output: {output:?}, size: {size:?}");
+    ) -> io::Result<Window> {
+        log::debug!("creating window output: {output:?}, size: {size:?}, span: {span:?}");
         let g = &state.g;
 
+        // only cover the configured span of the edge
+        let length = match pos {
+            Position::Left | Position::Right => size.1,
+            Position::Top | Position::Bottom => size.0,
+        };
+        let (start, end) = span.apply(0, length);
         let (width, height) = match pos {
-            Position::Left | Position::Right => (1, size.1 as u32),
-            Position::Top | Position::Bottom => (size.0 as u32, 1),
+            Position::Left | Position::Right => (1, (end - start) as u32),
+            Position::Top | Position::Bottom => ((end - start) as u32, 1),
         };
-        let mut file = tempfile::tempfile().unwrap();
-        draw(&mut file, (width, height));
+        let format = state
+            .buffer_format()
+            .ok_or_else(|| io::Error::new(ErrorKind::Unsupported, "no supported buffer format"))?;
+        let mut file = tempfile::tempfile()?;
+        draw(&mut file, (width, height), format)?;
         let pool = g
             .shm
             .create_pool(file.as_fd(), (width * height * 4) as i32, qh, ());
@@ -158,7 +234,7 @@ impl Window {
             width as i32,
             height as i32,
             (width * 4) as i32,
-            wl_shm::Format::Argb8888,
+            format,
             qh,
             (),
         );
@@ -173,27 +249,125 @@ impl Window {
             (),
         );
         let anchor = match pos {
-            Position::Left => Anchor::Left,
-            Position::Right => Anchor::Right,
-            Position::Top => Anchor::Top,
-            Position::Bottom => Anchor::Bottom,
+            Position::Left => Anchor::Left | Anchor::Top,
+            Position::Right => Anchor::Right | Anchor::Top,
+            Position::Top => Anchor::Top | Anchor::Left,
+            Position::Bottom => Anchor::Bottom | Anchor::Left,
+        };
+        let (top, left) = match pos {
+            Position::Left | Position::Right => (start, 0),
+            Position::Top | Position::Bottom => (0, start),
         };
 
         layer_surface.set_anchor(anchor);
         layer_surface.set_size(width, height);
         layer_surface.set_exclusive_zone(-1);
-        layer_surface.set_margin(0, 0, 0, 0);
+        layer_surface.set_margin(top, 0, 0, left);
         surface.set_input_region(None);
         surface.commit();
-        Window {
+        Ok(Window {
+            output: output.clone(),
             pos,
+            span,
+            section: EdgeSection { start, end, length },
+            size,
             buffer,
             surface,
             layer_surface,
+        })
+    }
+}
+
+impl Window {
+    /// output local coordinates of the surface local `point`
+    fn output_position(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let start = self.section.start as f64;
+        let (width, height) = (self.size.0 as f64, self.size.1 as f64);
+        match self.pos {
+            Position::Left => (x, start + y),
+            Position::Right => (width - 1. + x, start + y),
+            Position::Top => (start + x, y),
+            Position::Bottom => (start + x, height - 1. + y),
+        }
+    }
+}
+
+/// covered section of a screen edge, in pixels
+#[derive(Debug, Clone, Copy)]
+struct EdgeSection {
+    start: i32,
+    end: i32,
+    /// length of the edge
+    length: i32,
+}
+
+impl EdgeSection {
+    /// whether the section reaches the end of its edge towards `side`
+    fn reaches(&self, side: Position) -> bool {
+        match side {
+            Position::Left | Position::Top => self.start == 0,
+            Position::Right | Position::Bottom => self.end == self.length,
+        }
+    }
+
+    /// the corner the surface local `point` on edge `pos` is in, if any
+    fn corner(&self, pos: Position, (x, y): (f64, f64)) -> Option<Position> {
+        let (along, first, last) = match pos {
+            Position::Left | Position::Right => (y, Position::Top, Position::Bottom),
+            Position::Top | Position::Bottom => (x, Position::Left, Position::Right),
+        };
+        if self.reaches(first) && along < 1. {
+            Some(first)
+        } else if self.reaches(last) && along >= (self.end - self.start - 1) as f64 {
+            Some(last)
+        } else {
+            None
         }
     }
 }
 
+/// client the pointer entering an edge is handed off to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Handoff {
+    /// the client of the entered edge
+    Edge,
+    /// the client of the other edge meeting in this corner
+    Corner(Position),
+    /// neither, the pointer is in an ignored corner
+    Ignored(Position),
+}
+
+/// decide where the pointer entering `section` of edge `pos` at the
+/// surface local `point` is handed off to, given the direction of its
+/// last `motion` (output local), if known
+fn handoff(
+    mode: CornerHandoff,
+    pos: Position,
+    section: EdgeSection,
+    point: (f64, f64),
+    motion: Option<(f64, f64)>,
+) -> Handoff {
+    let Some(corner) = section.corner(pos, point) else {
+        return Handoff::Edge;
+    };
+    // whether the client on the left or right edge is picked
+    let horizontal = match mode {
+        CornerHandoff::Any => match motion {
+            Some((dx, dy)) if dx.abs() > dy.abs() => true,
+            Some((dx, dy)) if dy.abs() > dx.abs() => false,
+            _ => return Handoff::Edge,
+        },
+        CornerHandoff::Horizontal => true,
+        CornerHandoff::Vertical => false,
+        CornerHandoff::Ignore => return Handoff::Ignored(corner),
+    };
+    if horizontal == matches!(pos, Position::Left | Position::Right) {
+        Handoff::Edge
+    } else {
+        Handoff::Corner(corner)
+    }
+}
+
 impl Drop for Window {
     fn drop(&mut self) {
         log::debug!("destroying window!");
@@ -241,19 +415,51 @@ fn get_output_configuration(state: &State, pos: Position) -> Vec<(WlOutput, Outp
         .collect()
 }
 
-fn draw(f: &mut File, (width, height): (u32, u32)) {
+/// buffer formats in order of preference, formats with alpha keep the barriers invisible
+const SHM_FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Xbgr8888,
+];
+
+fn draw(f: &mut File, (width, height): (u32, u32), format: wl_shm::Format) -> io::Result<()> {
+    // AARRGGBB
+    let color: u32 = if env::var("LM_DEBUG_LAYER_SHELL").ok().is_some() {
+        0xff11d116
+    } else {
+        0x00000000
+    };
+    // AABBGGRR
+    let color = match format {
+        wl_shm::Format::Abgr8888 | wl_shm::Format::Xbgr8888 => {
+            let (r, b) = ((color >> 16) & 0xff, color & 0xff);
+            (color & 0xff00ff00) | (b << 16) | r
+        }
+        _ => color,
+    };
     let mut buf = BufWriter::new(f);
     for _ in 0..height {
         for _ in 0..width {
-            if env::var("LM_DEBUG_LAYER_SHELL").ok().is_some() {
-                // AARRGGBB
-                buf.write_all(&0xff11d116u32.to_ne_bytes()).unwrap();
-            } else {
-                // AARRGGBB
-                buf.write_all(&0x00000000u32.to_ne_bytes()).unwrap();
-            }
+            buf.write_all(&color.to_ne_bytes())?;
         }
     }
+    buf.flush()
+}
+
+/// whether the compositor advertises each of the globals required for capture,
+/// without creating any surfaces
+pub(crate) fn probe_globals(
+) -> std::result::Result<Vec<(&'static str, bool)>, LayerShellCaptureCreationError> {
+    let conn = Connection::connect_to_env()?;
+    let (g, _queue) = registry_queue_init::<State>(&conn)?;
+    let advertised: Vec<String> = g
+        .contents()
+        .with_list(|list| list.iter().map(|global| global.interface.clone()).collect());
+    Ok(REQUIRED_GLOBALS
+        .iter()
+        .map(|&name| (name, advertised.iter().any(|i| i == name)))
+        .collect())
 }
 
 impl LayerShellInputCapture {
@@ -297,8 +503,38 @@ impl LayerShellInputCapture {
                 to the client");
         }
         let shortcut_inhibit_manager = shortcut_inhibit_manager.ok();
+        // version 1 is the only version of pointer-constraints-unstable-v1
+        // and already includes the cursor position hint
+        log::debug!(
+            "bound wl_compositor v{}, wl_seat v{}, zwlr_layer_shell_v1 v{}, zwp_pointer_constraints_v1 v{}",
+            compositor.version(),
+            seat.version(),
+            layer_shell.version(),
+            pointer_constraints.version(),
+        );
+        // the fullscreen state was added in version 2
+        let foreign_toplevel_manager: Result<ZwlrForeignToplevelManagerV1, WaylandBindError> = g
+            .bind(&qh, 2..=3, ())
+            .map_err(|e| WaylandBindError::new(e, "zwlr_foreign_toplevel_manager_v1 2..=3"));
+        if let Err(e) = &foreign_toplevel_manager {
+            log::info!("foreign_toplevel_manager not supported: {e}\nfullscreen windows can not be detected");
+        }
+        let foreign_toplevel_manager = foreign_toplevel_manager.ok();
         let outputs = vec![];
 
+        // GlobalList::bind binds the first global with a matching interface
+        let global_names = g.contents().with_list(|globals| {
+            REQUIRED_GLOBALS
+                .iter()
+                .filter_map(|&interface| {
+                    globals
+                        .iter()
+                        .find(|g| g.interface == interface)
+                        .map(|g| (g.name, interface))
+                })
+                .collect()
+        });
+
         let g = Globals {
             compositor,
             shm,
@@ -309,6 +545,7 @@ impl LayerShellInputCapture {
             shortcut_inhibit_manager,
             outputs,
             xdg_output_manager,
+            foreign_toplevel_manager,
         };
 
         // flush outgoing events
@@ -325,12 +562,23 @@ impl LayerShellInputCapture {
             shortcut_inhibitor: None,
             active_windows: Vec::new(),
             focused: None,
+            pointer_position: None,
+            last_position: None,
             qh,
             wayland_fd,
             read_guard: None,
             pending_events: VecDeque::new(),
             output_info: vec![],
             scroll_discrete_pending: false,
+            shm_formats: vec![],
+            arming_delay: Duration::ZERO,
+            arming: None,
+            lock_lost: LockLost::default(),
+            corner_handoff: CornerHandoff::default(),
+            corner_pending: None,
+            global_names,
+            missing_globals: vec![],
+            toplevels: vec![],
         };
 
         // dispatch registry to () again, in order to read all wl_outputs
@@ -341,8 +589,22 @@ impl LayerShellInputCapture {
         queue.roundtrip(&mut state)?;
         log::debug!("==============> roundtrip 1 done");
 
+        // seat capabilities have been received by now
+        if state.pointer.is_none() {
+            return Err(LayerShellCaptureCreationError::NoSeat);
+        }
+
+        // as well as the supported buffer formats
+        match state.buffer_format() {
+            None => return Err(LayerShellCaptureCreationError::NoShmFormat),
+            Some(format @ (wl_shm::Format::Xrgb8888 | wl_shm::Format::Xbgr8888)) => {
+                log::warn!("compositor does not support transparent buffers ({format:?}), capture barriers are visible")
+            }
+            Some(format) => log::debug!("buffer format: {format:?}"),
+        }
+
         // read outputs
-        for output in state.g.outputs.iter() {
+        for (_, output) in state.g.outputs.iter() {
             state
                 .g
                 .xdg_output_manager
@@ -373,8 +635,12 @@ impl LayerShellInputCapture {
         Ok(LayerShellInputCapture(inner))
     }
 
-    fn add_client(&mut self, pos: Position) {
-        self.0.get_mut().state.add_client(pos);
+    fn add_client(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError> {
+        self.0
+            .get_mut()
+            .state
+            .add_client(pos, span)
+            .map_err(CaptureError::Shm)
     }
 
     fn delete_client(&mut self, pos: Position) {
@@ -416,6 +682,11 @@ impl State {
                 qh,
                 (),
             ));
+            // place the cursor where it entered when the lock is released
+            if let (Some(lock), Some((_, (x, y)))) = (&self.pointer_lock, &self.pointer_position) {
+                lock.set_cursor_position_hint(*x, *y);
+                window.surface.commit();
+            }
         }
 
         // request relative input
@@ -436,6 +707,108 @@ impl State {
         }
     }
 
+    /// window the pointer entering `window` at `position` is handed off
+    /// through, `None` if the capture is skipped because it is in a corner
+    fn resolve_corner(
+        &self,
+        window: &Arc<Window>,
+        position: (f64, f64),
+        motion: Option<(f64, f64)>,
+    ) -> Option<Arc<Window>> {
+        let handoff = handoff(
+            self.corner_handoff,
+            window.pos,
+            window.section,
+            position,
+            motion,
+        );
+        let (Handoff::Corner(corner) | Handoff::Ignored(corner)) = handoff else {
+            return Some(window.clone());
+        };
+        // window on the other edge meeting in this corner
+        let Some(other) = self.active_windows.iter().find(|w| {
+            w.pos == corner && w.output == window.output && w.section.reaches(window.pos)
+        }) else {
+            return Some(window.clone());
+        };
+        match handoff {
+            Handoff::Corner(_) => {
+                log::debug!(
+                    "corner: handing off to {} instead of {}",
+                    other.pos,
+                    window.pos
+                );
+                Some(other.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// record the pointer at the surface local `position` on `window`,
+    /// returns its motion since it was last seen on the same output
+    fn track_pointer(&mut self, window: &Window, position: (f64, f64)) -> Option<(f64, f64)> {
+        let now = Instant::now();
+        let (x, y) = window.output_position(position);
+        let motion = match self.last_position.take() {
+            Some((output, (last_x, last_y), time))
+                if output == window.output && now - time < MOTION_TIMEOUT =>
+            {
+                Some((x - last_x, y - last_y))
+            }
+            _ => None,
+        };
+        self.last_position = Some((window.output.clone(), (x, y), now));
+        motion
+    }
+
+    /// start capturing through the focused window, once the arming delay passed
+    fn enter(
+        &mut self,
+        surface: WlSurface,
+        pointer: &WlPointer,
+        serial: u32,
+        qh: &QueueHandle<State>,
+    ) {
+        let Some(pos) = self.focused.as_ref().map(|w| w.pos) else {
+            return;
+        };
+        // wait for the arming delay before grabbing the pointer
+        if !self.arming_delay.is_zero() {
+            self.arming = Some(Arming {
+                surface,
+                pointer: pointer.clone(),
+                serial,
+                timer: Box::pin(tokio::time::sleep(self.arming_delay)),
+            });
+            return;
+        }
+        self.grab(&surface, pointer, serial, qh);
+        self.pending_events.push_back((pos, CaptureEvent::Begin));
+    }
+
+    /// grab the pointer once the arming delay has passed,
+    /// returns true if it was grabbed
+    fn poll_arming(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some(arming) = self.arming.as_mut() else {
+            return false;
+        };
+        if arming.timer.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+        let Arming {
+            surface,
+            pointer,
+            serial,
+            ..
+        } = self.arming.take().expect("arming");
+        let Some(pos) = self.focused.as_ref().map(|w| w.pos) else {
+            return false;
+        };
+        self.grab(&surface, &pointer, serial, &self.qh.clone());
+        self.pending_events.push_back((pos, CaptureEvent::Begin));
+        true
+    }
+
     fn ungrab(&mut self) {
         // get focused client
         let window = match self.focused.as_ref() {
@@ -468,23 +841,107 @@ impl State {
         }
     }
 
-    fn add_client(&mut self, pos: Position) {
+    fn add_client(&mut self, pos: Position, span: EdgeSpan) -> io::Result<()> {
         let outputs = get_output_configuration(self, pos);
 
         log::debug!("outputs: {outputs:?}");
-        outputs.iter().for_each(|(o, i)| {
-            let window = Window::new(self, &self.qh, o, pos, i.size);
+        for (o, i) in outputs.iter() {
+            let window = Window::new(self, &self.qh, o, pos, span, i.size)?;
             let window = Arc::new(window);
             self.active_windows.push(window);
-        });
+        }
+        Ok(())
+    }
+
+    fn remove_global(&mut self, name: u32) {
+        if let Some(i) = self.g.outputs.iter().position(|&(n, _)| n == name) {
+            let (_, output) = self.g.outputs.remove(i);
+            log::info!("output removed, updating windows");
+            self.output_info.retain(|(o, _)| o != &output);
+            output.release();
+            self.update_windows();
+            return;
+        }
+        let Some(i) = self.global_names.iter().position(|&(n, _)| n == name) else {
+            return;
+        };
+        let (_, interface) = self.global_names.remove(i);
+        log::warn!("compositor removed {interface}, capture disabled until it is available again");
+        self.arming.take();
+        self.ungrab();
+        self.missing_globals.push(interface);
+    }
+
+    /// bind a required global that was removed before
+    fn rebind_global(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        interface: &str,
+        version: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(i) = self.missing_globals.iter().position(|&m| m == interface) else {
+            return;
+        };
+        let (min, max) = match interface {
+            "wl_compositor" => (4, 5),
+            "zwlr_layer_shell_v1" => (3, 4),
+            "wl_seat" => (7, 8),
+            _ => (1, 1),
+        };
+        if version < min {
+            log::warn!("{interface} version {version} is not supported (required: {min})");
+            return;
+        }
+        let version = version.min(max);
+        match interface {
+            "wl_compositor" => self.g.compositor = registry.bind(name, version, qh, ()),
+            "wl_shm" => self.g.shm = registry.bind(name, version, qh, ()),
+            "zwlr_layer_shell_v1" => self.g.layer_shell = registry.bind(name, version, qh, ()),
+            "wl_seat" => self.g.seat = registry.bind(name, version, qh, ()),
+            "zwp_pointer_constraints_v1" => {
+                self.g.pointer_constraints = registry.bind(name, version, qh, ())
+            }
+            "zwp_relative_pointer_manager_v1" => {
+                self.g.relative_pointer_manager = registry.bind(name, version, qh, ())
+            }
+            _ => return,
+        }
+        let interface = self.missing_globals.remove(i);
+        self.global_names.push((name, interface));
+        log::info!("{interface} is available again");
+        if self.missing_globals.is_empty() {
+            log::info!("re-enabling capture");
+            // windows may belong to a removed compositor or layer shell
+            self.update_windows();
+        }
+    }
+
+    /// preferred buffer format supported by the compositor
+    fn buffer_format(&self) -> Option<wl_shm::Format> {
+        SHM_FORMATS
+            .into_iter()
+            .find(|f| self.shm_formats.contains(f))
+    }
+
+    /// whether a focused window is fullscreen
+    fn fullscreen_active(&self) -> bool {
+        self.toplevels.iter().any(|t| t.fullscreen)
     }
 
     fn update_windows(&mut self) {
         log::debug!("updating windows");
         log::debug!("output info: {:?}", self.output_info);
-        let clients: Vec<_> = self.active_windows.drain(..).map(|w| w.pos).collect();
-        for pos in clients {
-            self.add_client(pos);
+        let clients: Vec<_> = self
+            .active_windows
+            .drain(..)
+            .map(|w| (w.pos, w.span))
+            .collect();
+        for (pos, span) in clients {
+            if let Err(e) = self.add_client(pos, span) {
+                log::warn!("{}", CaptureError::Shm(e));
+            }
         }
     }
 }
@@ -558,8 +1015,8 @@ impl Inner {
 
 #[async_trait]
 impl Capture for LayerShellInputCapture {
-    async fn create(&mut self, pos: Position) -> Result<(), CaptureError> {
-        self.add_client(pos);
+    async fn create(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError> {
+        self.add_client(pos, span)?;
         let inner = self.0.get_mut();
         Ok(inner.flush_events()?)
     }
@@ -573,10 +1030,27 @@ impl Capture for LayerShellInputCapture {
     async fn release(&mut self) -> Result<(), CaptureError> {
         log::debug!("releasing pointer");
         let inner = self.0.get_mut();
+        inner.state.arming.take();
         inner.state.ungrab();
         Ok(inner.flush_events()?)
     }
 
+    fn fullscreen_active(&self) -> bool {
+        self.0.get_ref().state.fullscreen_active()
+    }
+
+    fn set_lock_lost(&mut self, lock_lost: LockLost) {
+        self.0.get_mut().state.lock_lost = lock_lost;
+    }
+
+    fn set_arming_delay(&mut self, delay: Duration) {
+        self.0.get_mut().state.arming_delay = delay;
+    }
+
+    fn set_corner_handoff(&mut self, corner_handoff: CornerHandoff) {
+        self.0.get_mut().state.corner_handoff = corner_handoff;
+    }
+
     async fn terminate(&mut self) -> Result<(), CaptureError> {
         Ok(())
     }
@@ -586,11 +1060,24 @@ impl Stream for LayerShellInputCapture {
     type Item = Result<(Position, CaptureEvent), CaptureError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Some(event) = self.0.get_mut().state.pending_events.pop_front() {
-            return Poll::Ready(Some(Ok(event)));
-        }
-
         loop {
+            {
+                let inner = self.0.get_mut();
+
+                // grab the pointer once the arming delay has passed
+                if inner.state.poll_arming(cx) {
+                    if let Err(e) = inner.flush_events() {
+                        if e.kind() != ErrorKind::WouldBlock {
+                            return Poll::Ready(Some(Err(e.into())));
+                        }
+                    }
+                }
+
+                if let Some(event) = inner.state.pending_events.pop_front() {
+                    return Poll::Ready(Some(Ok(event)));
+                }
+            }
+
             let mut guard = ready!(self.0.poll_read_ready_mut(cx))?;
 
             {
@@ -626,11 +1113,8 @@ impl Stream for LayerShellInputCapture {
             // guard.clear_ready_matching(Ready::READABLE);
             guard.clear_ready();
 
-            // if an event has been queued during dispatch_events() we return it
-            match guard.get_inner_mut().state.pending_events.pop_front() {
-                Some(event) => return Poll::Ready(Some(Ok(event))),
-                None => continue,
-            }
+            // events queued during dispatch_events() are returned
+            // at the start of the next iteration
         }
     }
 }
@@ -648,17 +1132,38 @@ impl Dispatch<wl_seat::WlSeat, ()> for State {
             capabilities: WEnum::Value(capabilities),
         } = event
         {
-            if capabilities.contains(wl_seat::Capability::Pointer) {
-                if let Some(p) = state.pointer.take() {
+            // capabilities are announced again whenever the last pointer /
+            // keyboard device is removed or the first one is plugged in
+            let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+            match (has_pointer, state.pointer.take()) {
+                (true, None) => {
+                    log::info!("seat: pointer added");
+                    state.pointer = Some(seat.get_pointer(qh, ()));
+                }
+                (false, Some(p)) => {
+                    log::info!("seat: pointer removed");
+                    // relative motion and the lock belong to the removed pointer
+                    if state.pointer_lock.is_some() {
+                        if let Some(pos) = state.focused.as_ref().map(|w| w.pos) {
+                            state.ungrab();
+                            state.pending_events.push_back((pos, CaptureEvent::Lost));
+                        }
+                    }
                     p.release();
                 }
-                state.pointer.replace(seat.get_pointer(qh, ()));
+                (_, p) => state.pointer = p,
             }
-            if capabilities.contains(wl_seat::Capability::Keyboard) {
-                if let Some(k) = state.keyboard.take() {
+            let has_keyboard = capabilities.contains(wl_seat::Capability::Keyboard);
+            match (has_keyboard, state.keyboard.take()) {
+                (true, None) => {
+                    log::info!("seat: keyboard added");
+                    state.keyboard = Some(seat.get_keyboard(qh, ()));
+                }
+                (false, Some(k)) => {
+                    log::info!("seat: keyboard removed");
                     k.release();
                 }
-                seat.get_keyboard(qh, ());
+                (_, k) => state.keyboard = k,
             }
         }
     }
@@ -677,25 +1182,28 @@ impl Dispatch<WlPointer, ()> for State {
             wl_pointer::Event::Enter {
                 serial,
                 surface,
-                surface_x: _,
-                surface_y: _,
+                surface_x,
+                surface_y,
             } => {
+                let position = (surface_x, surface_y);
+                app.pointer_position = Some((surface.clone(), position));
                 // get client corresponding to the focused surface
-                {
-                    if let Some(window) = app.active_windows.iter().find(|w| w.surface == surface) {
-                        app.focused = Some(window.clone());
-                        app.grab(&surface, pointer, serial, qh);
-                    } else {
-                        return;
-                    }
+                let Some(window) = app.active_windows.iter().find(|w| w.surface == surface) else {
+                    return;
+                };
+                let window = window.clone();
+                let motion = app.track_pointer(&window, position);
+                // capture is disabled while required globals are missing
+                if !app.missing_globals.is_empty() {
+                    return;
                 }
-                let pos = app
-                    .active_windows
-                    .iter()
-                    .find(|w| w.surface == surface)
-                    .map(|w| w.pos)
-                    .unwrap();
-                app.pending_events.push_back((pos, CaptureEvent::Begin));
+                let Some(window) = app.resolve_corner(&window, position, motion) else {
+                    log::debug!("pointer entered an ignored corner");
+                    app.corner_pending = Some((surface, pointer.clone(), serial));
+                    return;
+                };
+                app.focused = Some(window);
+                app.enter(surface, pointer, serial, qh);
             }
             wl_pointer::Event::Leave { .. } => {
                 /* There are rare cases, where when a window is opened in
@@ -705,11 +1213,43 @@ impl Dispatch<WlPointer, ()> for State {
                  * it is impossible to grab it again (since the pointer
                  * lock, relative pointer,... objects are still in place)
                  */
+                app.pointer_position = None;
+                app.corner_pending = None;
+                if app.arming.take().is_some() {
+                    log::debug!("pointer left before the arming delay passed");
+                }
                 if app.pointer_lock.is_some() {
                     log::warn!("compositor released mouse");
                 }
                 app.ungrab();
             }
+            // only sent while the pointer is not locked
+            wl_pointer::Event::Motion {
+                time: _,
+                surface_x,
+                surface_y,
+            } => {
+                log::trace!("pointer at ({surface_x}, {surface_y})");
+                let position = (surface_x, surface_y);
+                let Some((surface, _)) = app.pointer_position.take() else {
+                    return;
+                };
+                app.pointer_position = Some((surface.clone(), position));
+                let window = app.active_windows.iter().find(|w| w.surface == surface);
+                let Some(window) = window.cloned() else {
+                    return;
+                };
+                let motion = app.track_pointer(&window, position);
+                // capture once the pointer moved out of an ignored corner
+                if let Some((surface, pointer, serial)) = app.corner_pending.take() {
+                    if app.resolve_corner(&window, position, motion).is_none() {
+                        app.corner_pending = Some((surface, pointer, serial));
+                        return;
+                    }
+                    app.focused = Some(window);
+                    app.enter(surface, &pointer, serial, qh);
+                }
+            }
             wl_pointer::Event::Button {
                 serial: _,
                 time,
@@ -755,7 +1295,7 @@ impl Dispatch<WlPointer, ()> for State {
                     })),
                 ));
             }
-            wl_pointer::Event::Frame {} => {
+            wl_pointer::Event::Frame => {
                 // TODO properly handle frame events
                 // we simply insert a frame event on the client side
                 // after each event for now
@@ -817,6 +1357,54 @@ impl Dispatch<WlKeyboard, ()> for State {
     }
 }
 
+impl Dispatch<ZwpLockedPointerV1, ()> for State {
+    fn event(
+        app: &mut Self,
+        lock: &ZwpLockedPointerV1,
+        event: <ZwpLockedPointerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_locked_pointer_v1::Event::Locked => log::debug!("pointer locked"),
+            // the lock is destroyed on release, so this is never caused by us
+            zwp_locked_pointer_v1::Event::Unlocked => {
+                if app.pointer_lock.as_ref() != Some(lock) {
+                    return;
+                }
+                match app.lock_lost {
+                    LockLost::Relock => {
+                        log::info!("compositor broke the pointer lock, locking again");
+                        lock.destroy();
+                        app.pointer_lock = None;
+                        let (Some(window), Some(pointer)) = (&app.focused, &app.pointer) else {
+                            return;
+                        };
+                        app.pointer_lock = Some(app.g.pointer_constraints.lock_pointer(
+                            &window.surface,
+                            pointer,
+                            None,
+                            Lifetime::Persistent,
+                            qh,
+                            (),
+                        ));
+                    }
+                    LockLost::Release => {
+                        log::info!("compositor broke the pointer lock, releasing capture");
+                        let Some(pos) = app.focused.as_ref().map(|w| w.pos) else {
+                            return;
+                        };
+                        app.ungrab();
+                        app.pending_events.push_back((pos, CaptureEvent::Lost));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 impl Dispatch<ZwpRelativePointerV1, ()> for State {
     fn event(
         app: &mut Self,
@@ -898,21 +1486,116 @@ impl Dispatch<wl_registry::WlRegistry, ()> for State {
                 name,
                 interface,
                 version: _,
-            } => {
-                if interface.as_str() == "wl_output" {
-                    log::debug!("wl_output global");
-                    state
-                        .g
-                        .outputs
-                        .push(registry.bind::<WlOutput, _, _>(name, 4, qh, ()))
+            } if interface.as_str() == "wl_output" => {
+                log::debug!("wl_output global");
+                state
+                    .g
+                    .outputs
+                    .push((name, registry.bind::<WlOutput, _, _>(name, 4, qh, ())))
+            }
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => state.rebind_global(registry, name, &interface, version, qh),
+            wl_registry::Event::GlobalRemove { name } => state.remove_global(name),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: <ZwlrForeignToplevelManagerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.toplevels.push(Toplevel {
+                    handle: toplevel,
+                    pending_fullscreen: false,
+                    fullscreen: false,
+                })
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                log::info!(
+                    "foreign toplevel manager finished, fullscreen windows can not be detected"
+                );
+                state.g.foreign_toplevel_manager.take();
+                state.toplevels.clear();
+            }
+            _ => {}
+        }
+    }
+
+    wayland_client::event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: <ZwlrForeignToplevelHandleV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(i) = state.toplevels.iter().position(|t| &t.handle == handle) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+                let states: Vec<u32> = states
+                    .chunks_exact(4)
+                    .map(|c| u32::from_ne_bytes(c.try_into().expect("chunk size")))
+                    .collect();
+                let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+                let fullscreen = zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32;
+                state.toplevels[i].pending_fullscreen =
+                    states.contains(&activated) && states.contains(&fullscreen);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                let toplevel = &mut state.toplevels[i];
+                if toplevel.fullscreen != toplevel.pending_fullscreen {
+                    log::debug!("fullscreen window focused: {}", toplevel.pending_fullscreen);
                 }
+                toplevel.fullscreen = toplevel.pending_fullscreen;
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                let toplevel = state.toplevels.remove(i);
+                toplevel.handle.destroy();
             }
-            wl_registry::Event::GlobalRemove { .. } => {}
             _ => {}
         }
     }
 }
 
+impl Dispatch<wl_shm::WlShm, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_shm::WlShm,
+        event: <wl_shm::WlShm as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_shm::Event::Format {
+            format: WEnum::Value(format),
+        } = event
+        {
+            if !state.shm_formats.contains(&format) {
+                state.shm_formats.push(format);
+            }
+        }
+    }
+}
+
 impl Dispatch<ZxdgOutputV1, WlOutput> for State {
     fn event(
         state: &mut Self,
@@ -975,8 +1658,139 @@ delegate_noop!(State: ZwpPointerConstraintsV1);
 
 // ignore events
 delegate_noop!(State: ignore ZxdgOutputManagerV1);
-delegate_noop!(State: ignore wl_shm::WlShm);
 delegate_noop!(State: ignore wl_buffer::WlBuffer);
 delegate_noop!(State: ignore WlSurface);
 delegate_noop!(State: ignore ZwpKeyboardShortcutsInhibitorV1);
-delegate_noop!(State: ignore ZwpLockedPointerV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: i32 = 1920;
+    const HEIGHT: i32 = 1080;
+
+    /// the edges meeting in each corner, with a surface local point in the corner
+    const CORNERS: [(Position, (f64, f64), Position); 8] = [
+        (Position::Left, (0.5, 0.5), Position::Top),
+        (Position::Top, (0.5, 0.5), Position::Left),
+        (Position::Right, (0.5, 0.5), Position::Top),
+        (Position::Top, (1919.5, 0.5), Position::Right),
+        (Position::Left, (0.5, 1079.5), Position::Bottom),
+        (Position::Bottom, (0.5, 0.5), Position::Left),
+        (Position::Right, (0.5, 1079.5), Position::Bottom),
+        (Position::Bottom, (1919.5, 0.5), Position::Right),
+    ];
+
+    fn full_edge(pos: Position) -> EdgeSection {
+        let length = match pos {
+            Position::Left | Position::Right => HEIGHT,
+            Position::Top | Position::Bottom => WIDTH,
+        };
+        EdgeSection {
+            start: 0,
+            end: length,
+            length,
+        }
+    }
+
+    fn is_horizontal(pos: Position) -> bool {
+        matches!(pos, Position::Left | Position::Right)
+    }
+
+    /// `Edge` if the picked client is the one of the entered edge
+    fn expected(pick_horizontal: bool, pos: Position, corner: Position) -> Handoff {
+        if pick_horizontal == is_horizontal(pos) {
+            Handoff::Edge
+        } else {
+            Handoff::Corner(corner)
+        }
+    }
+
+    #[test]
+    fn corner_fixed_modes() {
+        for (pos, point, corner) in CORNERS {
+            let edge = full_edge(pos);
+            for motion in [None, Some((-3., 1.)), Some((1., -3.))] {
+                assert_eq!(
+                    handoff(CornerHandoff::Horizontal, pos, edge, point, motion),
+                    expected(true, pos, corner),
+                    "horizontal, {pos} {corner}",
+                );
+                assert_eq!(
+                    handoff(CornerHandoff::Vertical, pos, edge, point, motion),
+                    expected(false, pos, corner),
+                    "vertical, {pos} {corner}",
+                );
+                assert_eq!(
+                    handoff(CornerHandoff::Ignore, pos, edge, point, motion),
+                    Handoff::Ignored(corner),
+                    "ignore, {pos} {corner}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn corner_any_follows_motion() {
+        for (pos, point, corner) in CORNERS {
+            let edge = full_edge(pos);
+            let any = |motion| handoff(CornerHandoff::Any, pos, edge, point, motion);
+            // direction unknown or ambiguous: the edge the compositor reported
+            assert_eq!(any(None), Handoff::Edge, "{pos} {corner}");
+            assert_eq!(any(Some((2., -2.))), Handoff::Edge, "{pos} {corner}");
+            // moving mostly sideways: the left or right client
+            assert_eq!(
+                any(Some((-3., 1.))),
+                expected(true, pos, corner),
+                "{pos} {corner}"
+            );
+            assert_eq!(
+                any(Some((3., -1.))),
+                expected(true, pos, corner),
+                "{pos} {corner}"
+            );
+            // moving mostly up or down: the top or bottom client
+            assert_eq!(
+                any(Some((1., -3.))),
+                expected(false, pos, corner),
+                "{pos} {corner}"
+            );
+            assert_eq!(
+                any(Some((-1., 3.))),
+                expected(false, pos, corner),
+                "{pos} {corner}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_corner() {
+        let modes = [
+            CornerHandoff::Any,
+            CornerHandoff::Horizontal,
+            CornerHandoff::Vertical,
+            CornerHandoff::Ignore,
+        ];
+        for mode in modes {
+            // in the middle of the edge
+            let edge = full_edge(Position::Left);
+            let point = (0.5, 540.);
+            assert_eq!(
+                handoff(mode, Position::Left, edge, point, Some((0., -3.))),
+                Handoff::Edge
+            );
+            // the section does not reach the end of the edge
+            let edge = EdgeSection {
+                start: 100,
+                end: 980,
+                length: HEIGHT,
+            };
+            for point in [(0.5, 0.5), (0.5, 879.5)] {
+                assert_eq!(
+                    handoff(mode, Position::Right, edge, point, Some((0., -3.))),
+                    Handoff::Edge
+                );
+            }
+        }
+    }
+}
@@ -3,13 +3,14 @@ use std::{
     fmt::Display,
     mem::swap,
     task::{ready, Poll},
+    time::Duration,
 };
 
 use async_trait::async_trait;
 use futures::StreamExt;
 use futures_core::Stream;
 
-use input_event::{scancode, Event, KeyboardEvent};
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
 
 pub use error::{CaptureCreationError, CaptureError, InputCaptureError};
 
@@ -41,6 +42,8 @@ pub enum CaptureEvent {
     Begin,
     /// input event coming from capture handle
     Input(Event),
+    /// the capture was ended by the compositor, not by a release request
+    Lost,
 }
 
 impl Display for CaptureEvent {
@@ -48,6 +51,7 @@ impl Display for CaptureEvent {
         match self {
             CaptureEvent::Begin => write!(f, "begin capture"),
             CaptureEvent::Input(e) => write!(f, "{e}"),
+            CaptureEvent::Lost => write!(f, "capture lost"),
         }
     }
 }
@@ -83,6 +87,96 @@ impl Display for Position {
     }
 }
 
+/// where the local cursor is placed when a capture is released
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorReturn {
+    /// the point at which the cursor left the screen
+    #[default]
+    Exit,
+    /// the edge opposite to the one the cursor left through
+    OppositeEdge,
+    /// the center of the screen the cursor left
+    Center,
+}
+
+/// what happens when the compositor breaks the pointer lock
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LockLost {
+    /// release the capture and report [`CaptureEvent::Lost`]
+    #[default]
+    Release,
+    /// lock the pointer again right away
+    Relock,
+}
+
+/// which client the cursor is handed off to when it leaves
+/// through a corner where the edges of two clients meet
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CornerHandoff {
+    /// the edge the pointer moves towards, if known,
+    /// otherwise whichever edge the compositor reports it on.
+    /// The direction is only known if the pointer was seen on an edge
+    /// shortly before, so this is best-effort.
+    Any,
+    /// the client on the left or right edge
+    #[default]
+    Horizontal,
+    /// the client on the top or bottom edge
+    Vertical,
+    /// neither, the cursor has to move away from the corner
+    Ignore,
+}
+
+/// section of a screen edge that triggers a capture,
+/// given as fractions along the edge (left to right / top to bottom)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeSpan {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl EdgeSpan {
+    pub fn new(start: f64, end: f64) -> Self {
+        let span = Self::clamped(start, end);
+        if span.start != start || span.end != end {
+            log::warn!(
+                "invalid edge span [{start}, {end}] (expected 0 <= start <= end <= 1), using [{}, {}]",
+                span.start,
+                span.end
+            );
+        }
+        span
+    }
+
+    fn clamped(start: f64, end: f64) -> Self {
+        let start = if start.is_nan() {
+            0.
+        } else {
+            start.clamp(0., 1.)
+        };
+        let end = if end.is_nan() {
+            1.
+        } else {
+            end.clamp(start, 1.)
+        };
+        Self { start, end }
+    }
+
+    /// start and end coordinate of the span on an edge
+    /// beginning at `offset` with the given length
+    pub fn apply(&self, offset: i32, len: i32) -> (i32, i32) {
+        let start = offset + (self.start * len as f64) as i32;
+        let end = offset + (self.end * len as f64) as i32;
+        (start, end.max(start + 1))
+    }
+}
+
+impl Default for EdgeSpan {
+    fn default() -> Self {
+        Self { start: 0., end: 1. }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Backend {
     #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
@@ -98,6 +192,29 @@ pub enum Backend {
     Dummy,
 }
 
+/// capture backends in order of preference
+pub const BACKENDS: &[Backend] = &[
+    #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+    Backend::InputCapturePortal,
+    #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
+    Backend::LayerShell,
+    #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
+    Backend::X11,
+    #[cfg(windows)]
+    Backend::Windows,
+    #[cfg(target_os = "macos")]
+    Backend::MacOs,
+];
+
+impl Backend {
+    /// the backend following this one in order of preference, wrapping around
+    pub fn next(self) -> Self {
+        let i = BACKENDS.iter().position(|&b| b == self);
+        let next = i.map(|i| (i + 1) % BACKENDS.len()).unwrap_or(0);
+        BACKENDS.get(next).copied().unwrap_or(Backend::Dummy)
+    }
+}
+
 impl Display for Backend {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -119,38 +236,62 @@ impl Display for Backend {
 pub struct InputCapture {
     /// capture backend
     capture: Box<dyn Capture>,
+    /// the backend in use
+    backend: Backend,
     /// keys pressed by active capture
     pressed_keys: HashSet<scancode::Linux>,
+    /// mouse buttons pressed by active capture
+    pressed_buttons: HashSet<u32>,
+    /// depressed modifiers reported by active capture
+    modifiers: u32,
     /// map from position to ids
     position_map: HashMap<Position, Vec<CaptureHandle>>,
     /// map from id to position
     id_map: HashMap<CaptureHandle, Position>,
+    /// edge span of the capture at each position
+    spans: HashMap<Position, EdgeSpan>,
     /// pending events
     pending: VecDeque<(CaptureHandle, CaptureEvent)>,
 }
 
 impl InputCapture {
-    /// create a new client with the given id
-    pub async fn create(&mut self, id: CaptureHandle, pos: Position) -> Result<(), CaptureError> {
+    /// create a new client with the given id,
+    /// triggered by the given span of the edge at `pos`
+    pub async fn create(
+        &mut self,
+        id: CaptureHandle,
+        pos: Position,
+        span: EdgeSpan,
+    ) -> Result<(), CaptureError> {
         assert!(!self.id_map.contains_key(&id));
 
-        self.id_map.insert(id, pos);
-
         if let Some(v) = self.position_map.get_mut(&pos) {
+            // all clients at a position share one barrier
+            let existing = self.spans[&pos];
+            if existing != span {
+                return Err(CaptureError::SpanConflict {
+                    pos,
+                    span,
+                    existing,
+                });
+            }
             v.push(id);
+            self.id_map.insert(id, pos);
             Ok(())
         } else {
+            self.id_map.insert(id, pos);
             self.position_map.insert(pos, vec![id]);
-            self.capture.create(pos).await
+            self.spans.insert(pos, span);
+            self.capture.create(pos, span).await
         }
     }
 
     /// destroy the client with the given id, if it exists
     pub async fn destroy(&mut self, id: CaptureHandle) -> Result<(), CaptureError> {
-        let pos = self
-            .id_map
-            .remove(&id)
-            .expect("no position for this handle");
+        // e.g. rejected because of a conflicting span
+        let Some(pos) = self.id_map.remove(&id) else {
+            return Ok(());
+        };
 
         log::debug!("destroying capture {id} @ {pos}");
         let remaining = self.position_map.get_mut(&pos).expect("id vector");
@@ -160,6 +301,7 @@ impl InputCapture {
         if remaining.is_empty() {
             log::debug!("destroying capture @ {pos} - no remaining ids");
             self.position_map.remove(&pos);
+            self.spans.remove(&pos);
             self.capture.destroy(pos).await?;
         }
         Ok(())
@@ -168,6 +310,8 @@ impl InputCapture {
     /// release mouse
     pub async fn release(&mut self) -> Result<(), CaptureError> {
         self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.modifiers = 0;
         self.capture.release().await
     }
 
@@ -178,21 +322,106 @@ impl InputCapture {
 
     /// creates a new [`InputCapture`]
     pub async fn new(backend: Option<Backend>) -> Result<Self, CaptureCreationError> {
-        let capture = create(backend).await?;
+        let (capture, backend) = create(backend).await?;
         Ok(Self {
             capture,
+            backend,
             id_map: Default::default(),
             pending: Default::default(),
             position_map: Default::default(),
             pressed_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            modifiers: 0,
+            spans: Default::default(),
         })
     }
 
+    /// set where the cursor is placed when the capture is released.
+    /// This is a hint: backends that can not warp the cursor
+    /// leave it at the exit point.
+    pub fn set_cursor_return(&mut self, cursor_return: CursorReturn) {
+        self.capture.set_cursor_return(cursor_return);
+    }
+
+    /// set the time the pointer has to rest at the edge before it is captured.
+    /// Leaving the edge before the delay has passed cancels the capture.
+    pub fn set_arming_delay(&mut self, delay: Duration) {
+        self.capture.set_arming_delay(delay);
+    }
+
+    /// set what happens when the compositor breaks the pointer lock
+    pub fn set_lock_lost(&mut self, lock_lost: LockLost) {
+        self.capture.set_lock_lost(lock_lost);
+    }
+
+    /// set which client the cursor is handed off to in a corner
+    pub fn set_corner_handoff(&mut self, corner_handoff: CornerHandoff) {
+        self.capture.set_corner_handoff(corner_handoff);
+    }
+
+    /// check whether the given backend is available
+    /// by creating it without any capture,
+    /// the portal is only checked for the input capture interface
+    pub async fn probe(backend: Backend) -> Result<(), CaptureCreationError> {
+        #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+        if backend == Backend::InputCapturePortal {
+            return Ok(libei::probe().await?);
+        }
+        let mut capture = create_backend(backend).await?;
+        if let Err(e) = capture.terminate().await {
+            log::warn!("failed to terminate {backend} input capture: {e}");
+        }
+        Ok(())
+    }
+
+    /// whether the compositor advertises each of the globals
+    /// the layer-shell backend requires
+    #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
+    pub fn layer_shell_globals() -> Result<Vec<(&'static str, bool)>, CaptureCreationError> {
+        Ok(layer_shell::probe_globals()?)
+    }
+
+    /// whether the backend reports the modifier state
+    /// right after a capture begins
+    pub fn reports_modifiers(&self) -> bool {
+        #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
+        if self.backend == Backend::LayerShell {
+            return true;
+        }
+        false
+    }
+
+    /// whether a fullscreen window is focused on this device,
+    /// always `false` if the backend can not detect fullscreen windows
+    pub fn fullscreen_active(&self) -> bool {
+        self.capture.fullscreen_active()
+    }
+
+    /// the backend used by this [`InputCapture`]
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
     /// check whether the given keys are pressed
     pub fn keys_pressed(&self, keys: &[scancode::Linux]) -> bool {
         keys.iter().all(|k| self.pressed_keys.contains(k))
     }
 
+    /// keys pressed by active capture
+    pub fn pressed_keys(&self) -> impl Iterator<Item = scancode::Linux> + '_ {
+        self.pressed_keys.iter().copied()
+    }
+
+    /// mouse buttons pressed by active capture
+    pub fn pressed_buttons(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pressed_buttons.iter().copied()
+    }
+
+    /// depressed modifiers, 0 if the backend does not report them
+    pub fn modifiers(&self) -> u32 {
+        self.modifiers
+    }
+
     fn update_pressed_keys(&mut self, key: u32, state: u8) {
         if let Ok(scancode) = scancode::Linux::try_from(key) {
             log::debug!("key: {key}, state: {state}, scancode: {scancode:?}");
@@ -231,8 +460,22 @@ impl Stream for InputCapture {
         };
 
         // handle key presses
-        if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, state, .. })) = event {
-            self.update_pressed_keys(key, state);
+        match event {
+            CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, state, .. })) => {
+                self.update_pressed_keys(key, state);
+            }
+            CaptureEvent::Input(Event::Pointer(PointerEvent::Button { button, state, .. })) => {
+                match state {
+                    1 => self.pressed_buttons.insert(button),
+                    _ => self.pressed_buttons.remove(&button),
+                };
+            }
+            CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Modifiers {
+                depressed, ..
+            })) => {
+                self.modifiers = depressed;
+            }
+            _ => {}
         }
 
         let len = self
@@ -266,7 +509,7 @@ impl Stream for InputCapture {
 #[async_trait]
 trait Capture: Stream<Item = Result<(Position, CaptureEvent), CaptureError>> + Unpin {
     /// create a new client with the given id
-    async fn create(&mut self, pos: Position) -> Result<(), CaptureError>;
+    async fn create(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError>;
 
     /// destroy the client with the given id, if it exists
     async fn destroy(&mut self, pos: Position) -> Result<(), CaptureError>;
@@ -274,6 +517,39 @@ trait Capture: Stream<Item = Result<(Position, CaptureEvent), CaptureError>> + U
     /// release mouse
     async fn release(&mut self) -> Result<(), CaptureError>;
 
+    /// set where the cursor is placed on release
+    fn set_cursor_return(&mut self, cursor_return: CursorReturn) {
+        if cursor_return != CursorReturn::Exit {
+            log::warn!("backend can not warp the cursor on release, returning to exit point");
+        }
+    }
+
+    /// set the time the pointer has to rest at the edge before it is captured
+    fn set_arming_delay(&mut self, delay: Duration) {
+        if !delay.is_zero() {
+            log::warn!("backend does not support an arming delay, capturing immediately");
+        }
+    }
+
+    /// set what happens when the compositor breaks the pointer lock
+    fn set_lock_lost(&mut self, lock_lost: LockLost) {
+        if lock_lost != LockLost::Release {
+            log::warn!("backend does not lock the pointer, lock_lost is ignored");
+        }
+    }
+
+    /// set which client the cursor is handed off to in a corner
+    fn set_corner_handoff(&mut self, corner_handoff: CornerHandoff) {
+        if corner_handoff != CornerHandoff::default() {
+            log::warn!("backend does not support corner_handoff, ignoring it");
+        }
+    }
+
+    /// whether a fullscreen window is focused
+    fn fullscreen_active(&self) -> bool {
+        false
+    }
+
     /// destroy the input capture
     async fn terminate(&mut self) -> Result<(), CaptureError>;
 }
@@ -302,7 +578,10 @@ async fn create_backend(
 async fn create(
     backend: Option<Backend>,
 ) -> Result<
-    Box<dyn Capture<Item = Result<(Position, CaptureEvent), CaptureError>>>,
+    (
+        Box<dyn Capture<Item = Result<(Position, CaptureEvent), CaptureError>>>,
+        Backend,
+    ),
     CaptureCreationError,
 > {
     if let Some(backend) = backend {
@@ -310,25 +589,14 @@ async fn create(
         if b.is_ok() {
             log::info!("using capture backend: {backend}");
         }
-        return b;
+        return b.map(|b| (b, backend));
     }
 
-    for backend in [
-        #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
-        Backend::InputCapturePortal,
-        #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
-        Backend::LayerShell,
-        #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
-        Backend::X11,
-        #[cfg(windows)]
-        Backend::Windows,
-        #[cfg(target_os = "macos")]
-        Backend::MacOs,
-    ] {
+    for &backend in BACKENDS {
         match create_backend(backend).await {
             Ok(b) => {
                 log::info!("using capture backend: {backend}");
-                return Ok(b);
+                return Ok((b, backend));
             }
             Err(e) if e.cancelled_by_user() => return Err(e),
             Err(e) => log::warn!("{backend} input capture backend unavailable: {e}"),
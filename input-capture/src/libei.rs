@@ -23,7 +23,7 @@ use std::{
     os::unix::net::UnixStream,
     pin::Pin,
     rc::Rc,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 use tokio::{
@@ -43,7 +43,7 @@ use crate::CaptureEvent;
 
 use super::{
     error::{CaptureError, LibeiCaptureCreationError},
-    Capture as LanMouseInputCapture, Position,
+    Capture as LanMouseInputCapture, CursorReturn, EdgeSpan, Position,
 };
 
 /* there is a bug in xdg-remote-desktop-portal-gnome / mutter that
@@ -53,7 +53,7 @@ use super::{
 /// events that necessitate restarting the capture session
 #[derive(Clone, Copy, Debug)]
 enum LibeiNotifyEvent {
-    Create(Position),
+    Create(Position, EdgeSpan),
     Destroy(Position),
 }
 
@@ -65,18 +65,74 @@ pub struct LibeiInputCapture<'a> {
     notify_capture: Sender<LibeiNotifyEvent>,
     notify_release: Arc<Notify>,
     cancellation_token: CancellationToken,
+    cursor_return: Arc<Mutex<CursorReturn>>,
     terminated: bool,
 }
 
 /// returns (start pos, end pos), inclusive
-fn pos_to_barrier(r: &Region, pos: Position) -> (i32, i32, i32, i32) {
+fn pos_to_barrier(r: &Region, pos: Position, span: EdgeSpan) -> (i32, i32, i32, i32) {
     let (x, y) = (r.x_offset(), r.y_offset());
     let (w, h) = (r.width() as i32, r.height() as i32);
+    let (y1, y2) = span.apply(y, h);
+    let (x1, x2) = span.apply(x, w);
     match pos {
-        Position::Left => (x, y, x, y + h - 1),
-        Position::Right => (x + w, y, x + w, y + h - 1),
-        Position::Top => (x, y, x + w - 1, y),
-        Position::Bottom => (x, y + h, x + w - 1, y + h),
+        Position::Left => (x, y1, x, y2 - 1),
+        Position::Right => (x + w, y1, x + w, y2 - 1),
+        Position::Top => (x1, y, x2 - 1, y),
+        Position::Bottom => (x1, y + h, x2 - 1, y + h),
+    }
+}
+
+/// (x, y, width, height) of a region
+type Rect = (i32, i32, i32, i32);
+
+fn region_rect(r: &Region) -> Rect {
+    (
+        r.x_offset(),
+        r.y_offset(),
+        r.width() as i32,
+        r.height() as i32,
+    )
+}
+
+/// where to place the cursor when releasing a capture
+/// that was entered at `(x, y)` through the edge at `pos`
+fn return_position(
+    regions: &[Rect],
+    (x, y): (f64, f64),
+    pos: Position,
+    cursor_return: CursorReturn,
+) -> (f64, f64) {
+    let (dx, dy) = match pos {
+        // offset cursor position to not enter again immediately
+        Position::Left => (1., 0.),
+        Position::Right => (-1., 0.),
+        Position::Top => (0., 1.),
+        Position::Bottom => (0., -1.),
+    };
+    // release 1px to the right of the entered zone
+    let exit = (x + dx, y + dy);
+    if cursor_return == CursorReturn::Exit {
+        return exit;
+    }
+    // region the cursor left (barriers lie on the region boundary)
+    let region = regions.iter().copied().find(|&(rx, ry, w, h)| {
+        (rx as f64..=(rx + w) as f64).contains(&x) && (ry as f64..=(ry + h) as f64).contains(&y)
+    });
+    let Some((rx, ry, w, h)) = region else {
+        log::warn!("no region found for cursor position ({x}, {y})");
+        return exit;
+    };
+    let (rx, ry, w, h) = (rx as f64, ry as f64, w as f64, h as f64);
+    match cursor_return {
+        CursorReturn::Exit => exit,
+        CursorReturn::Center => (rx + w / 2., ry + h / 2.),
+        CursorReturn::OppositeEdge => match pos {
+            Position::Left => (rx + w - 1., y),
+            Position::Right => (rx, y),
+            Position::Top => (x, ry + h - 1.),
+            Position::Bottom => (x, ry),
+        },
     }
 }
 
@@ -104,13 +160,13 @@ impl From<ICBarrier> for Barrier {
 
 fn select_barriers(
     zones: &Zones,
-    clients: &[Position],
+    clients: &[(Position, EdgeSpan)],
     next_barrier_id: &mut NonZeroU32,
 ) -> (Vec<ICBarrier>, HashMap<BarrierID, Position>) {
     let mut pos_for_barrier = HashMap::new();
     let mut barriers: Vec<ICBarrier> = vec![];
 
-    for pos in clients {
+    for (pos, span) in clients {
         let mut client_barriers = zones
             .regions()
             .iter()
@@ -119,7 +175,7 @@ fn select_barriers(
                 *next_barrier_id = next_barrier_id
                     .checked_add(1)
                     .expect("barrier id out of range");
-                let position = pos_to_barrier(r, *pos);
+                let position = pos_to_barrier(r, *pos, *span);
                 pos_for_barrier.insert(id, *pos);
                 ICBarrier::new(id, position)
             })
@@ -132,11 +188,12 @@ fn select_barriers(
 async fn update_barriers(
     input_capture: &InputCapture<'_>,
     session: &Session<'_, InputCapture<'_>>,
-    active_clients: &[Position],
+    active_clients: &[(Position, EdgeSpan)],
     next_barrier_id: &mut NonZeroU32,
-) -> Result<(Vec<ICBarrier>, HashMap<BarrierID, Position>), ashpd::Error> {
+) -> Result<(Vec<ICBarrier>, HashMap<BarrierID, Position>, Vec<Rect>), ashpd::Error> {
     let zones = input_capture.zones(session).await?.response()?;
     log::debug!("zones: {zones:?}");
+    let regions = zones.regions().iter().map(region_rect).collect();
 
     let (barriers, id_map) = select_barriers(&zones, active_clients, next_barrier_id);
     log::debug!("barriers: {barriers:?}");
@@ -148,12 +205,12 @@ async fn update_barriers(
         .await?;
     let response = response.response()?;
     log::debug!("{response:?}");
-    Ok((barriers, id_map))
+    Ok((barriers, id_map, regions))
 }
 
 async fn create_session<'a>(
     input_capture: &'a InputCapture<'a>,
-) -> std::result::Result<(Session<'_, InputCapture<'_>>, BitFlags<Capabilities>), ashpd::Error> {
+) -> std::result::Result<(Session<'a, InputCapture<'a>>, BitFlags<Capabilities>), ashpd::Error> {
     log::debug!("creating input capture session");
     input_capture
         .create_session(
@@ -201,6 +258,13 @@ async fn libei_event_handler(
     }
 }
 
+/// check that the input capture portal is available without creating a session,
+/// which could ask the user for permission
+pub(crate) async fn probe() -> std::result::Result<(), LibeiCaptureCreationError> {
+    InputCapture::new().await?;
+    Ok(())
+}
+
 impl<'a> LibeiInputCapture<'a> {
     pub async fn new() -> std::result::Result<Self, LibeiCaptureCreationError> {
         let input_capture = Box::pin(InputCapture::new().await?);
@@ -212,6 +276,7 @@ impl<'a> LibeiInputCapture<'a> {
         let notify_release = Arc::new(Notify::new());
 
         let cancellation_token = CancellationToken::new();
+        let cursor_return = Arc::new(Mutex::new(CursorReturn::default()));
 
         let capture = do_capture(
             input_capture_ptr,
@@ -220,6 +285,7 @@ impl<'a> LibeiInputCapture<'a> {
             first_session,
             event_tx,
             cancellation_token.clone(),
+            cursor_return.clone(),
         );
         let capture_task = tokio::task::spawn_local(capture);
 
@@ -230,6 +296,7 @@ impl<'a> LibeiInputCapture<'a> {
             notify_capture,
             notify_release,
             cancellation_token,
+            cursor_return,
             terminated: false,
         };
 
@@ -244,12 +311,13 @@ async fn do_capture(
     session: Option<(Session<'_, InputCapture<'_>>, BitFlags<Capabilities>)>,
     event_tx: Sender<(Position, CaptureEvent)>,
     cancellation_token: CancellationToken,
+    cursor_return: Arc<Mutex<CursorReturn>>,
 ) -> Result<(), CaptureError> {
     let mut session = session.map(|s| s.0);
 
     /* safety: libei_task does not outlive Self */
     let input_capture = unsafe { &*input_capture };
-    let mut active_clients: Vec<Position> = vec![];
+    let mut active_clients: Vec<(Position, EdgeSpan)> = vec![];
     let mut next_barrier_id = NonZeroU32::new(1).expect("id must be non-zero");
 
     let mut zones_changed = input_capture.receive_zones_changed().await?;
@@ -298,7 +366,7 @@ async fn do_capture(
                 &event_tx,
                 &active_clients,
                 &mut next_barrier_id,
-                &notify_release,
+                (&notify_release, &cursor_return),
                 (cancel_session.clone(), cancel_update.clone()),
             );
 
@@ -323,8 +391,8 @@ async fn do_capture(
         // update clients if requested
         if let Some(event) = capture_event_occured.take() {
             match event {
-                LibeiNotifyEvent::Create(p) => active_clients.push(p),
-                LibeiNotifyEvent::Destroy(p) => active_clients.retain(|&pos| pos != p),
+                LibeiNotifyEvent::Create(p, s) => active_clients.push((p, s)),
+                LibeiNotifyEvent::Destroy(p) => active_clients.retain(|&(pos, _)| pos != p),
             }
         }
 
@@ -339,11 +407,12 @@ async fn do_capture_session(
     input_capture: &InputCapture<'_>,
     session: &mut Session<'_, InputCapture<'_>>,
     event_tx: &Sender<(Position, CaptureEvent)>,
-    active_clients: &[Position],
+    active_clients: &[(Position, EdgeSpan)],
     next_barrier_id: &mut NonZeroU32,
-    notify_release: &Notify,
+    release: (&Notify, &Mutex<CursorReturn>),
     cancel: (CancellationToken, CancellationToken),
 ) -> Result<(), CaptureError> {
+    let (notify_release, cursor_return) = release;
     let (cancel_session, cancel_update) = cancel;
     // current client
     let current_pos = Rc::new(Cell::new(None));
@@ -352,7 +421,7 @@ async fn do_capture_session(
     let (context, _conn, ei_event_stream) = connect_to_eis(input_capture, session).await?;
 
     // set barriers
-    let (barriers, pos_for_barrier_id) =
+    let (barriers, pos_for_barrier_id, regions) =
         update_barriers(input_capture, session, active_clients, next_barrier_id).await?;
 
     log::debug!("enabling session");
@@ -423,7 +492,16 @@ async fn do_capture_session(
                         },
                     }
 
-                    release_capture(input_capture, session, activated, pos).await?;
+                    let cursor_return = *cursor_return.lock().expect("lock");
+                    release_capture(
+                        input_capture,
+                        session,
+                        activated,
+                        pos,
+                        &regions,
+                        cursor_return,
+                    )
+                    .await?;
 
                 }
                 _ = notify_release.notified() => { /* capture release -> we are not capturing anyway, so ignore */
@@ -467,6 +545,8 @@ async fn release_capture<'a>(
     session: &Session<'a, InputCapture<'a>>,
     activated: Activated,
     current_pos: Position,
+    regions: &[Rect],
+    cursor_return: CursorReturn,
 ) -> Result<(), CaptureError> {
     if let Some(activation_id) = activated.activation_id() {
         log::debug!("releasing input capture {activation_id}");
@@ -475,15 +555,11 @@ async fn release_capture<'a>(
         .cursor_position()
         .expect("compositor did not report cursor position!");
     log::debug!("client entered @ ({x}, {y})");
-    let (dx, dy) = match current_pos {
-        // offset cursor position to not enter again immediately
-        Position::Left => (1., 0.),
-        Position::Right => (-1., 0.),
-        Position::Top => (0., 1.),
-        Position::Bottom => (0., -1.),
-    };
-    // release 1px to the right of the entered zone
-    let cursor_position = (x as f64 + dx, y as f64 + dy);
+    // the compositor may ignore the requested position,
+    // in which case the cursor stays at the exit point
+    let cursor_position =
+        return_position(regions, (x as f64, y as f64), current_pos, cursor_return);
+    log::debug!("releasing cursor @ {cursor_position:?} ({cursor_return:?})");
     input_capture
         .release(session, activated.activation_id(), Some(cursor_position))
         .await?;
@@ -562,10 +638,10 @@ async fn handle_ei_event(
 
 #[async_trait]
 impl<'a> LanMouseInputCapture for LibeiInputCapture<'a> {
-    async fn create(&mut self, pos: Position) -> Result<(), CaptureError> {
+    async fn create(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError> {
         let _ = self
             .notify_capture
-            .send(LibeiNotifyEvent::Create(pos))
+            .send(LibeiNotifyEvent::Create(pos, span))
             .await;
         Ok(())
     }
@@ -583,6 +659,10 @@ impl<'a> LanMouseInputCapture for LibeiInputCapture<'a> {
         Ok(())
     }
 
+    fn set_cursor_return(&mut self, cursor_return: CursorReturn) {
+        *self.cursor_return.lock().expect("lock") = cursor_return;
+    }
+
     async fn terminate(&mut self) -> Result<(), CaptureError> {
         self.cancellation_token.cancel();
         let task = &mut self.capture_task;
@@ -1,4 +1,6 @@
-use super::{error::MacosCaptureCreationError, Capture, CaptureError, CaptureEvent, Position};
+use super::{
+    error::MacosCaptureCreationError, Capture, CaptureError, CaptureEvent, EdgeSpan, Position,
+};
 use async_trait::async_trait;
 use bitflags::bitflags;
 use core_foundation::base::{kCFAllocatorDefault, CFRelease};
@@ -507,7 +509,10 @@ impl MacOSInputCapture {
 
 #[async_trait]
 impl Capture for MacOSInputCapture {
-    async fn create(&mut self, pos: Position) -> Result<(), CaptureError> {
+    async fn create(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError> {
+        if span != EdgeSpan::default() {
+            log::warn!("backend does not support edge spans, capturing along the whole edge");
+        }
         let notify_tx = self.notify_tx.clone();
         tokio::task::spawn_local(async move {
             log::debug!("creating capture, {pos}");
@@ -37,7 +37,7 @@ use input_event::{
     Event, KeyboardEvent, PointerEvent, BTN_BACK, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT,
 };
 
-use super::{Capture, CaptureError, CaptureEvent, Position};
+use super::{Capture, CaptureError, CaptureEvent, EdgeSpan, Position};
 
 enum Request {
     Create(Position),
@@ -65,7 +65,10 @@ unsafe fn signal_message_thread(event_type: EventType) {
 
 #[async_trait]
 impl Capture for WindowsInputCapture {
-    async fn create(&mut self, pos: Position) -> Result<(), CaptureError> {
+    async fn create(&mut self, pos: Position, span: EdgeSpan) -> Result<(), CaptureError> {
+        if span != EdgeSpan::default() {
+            log::warn!("backend does not support edge spans, capturing along the whole edge");
+        }
         unsafe {
             {
                 let mut requests = REQUEST_BUFFER.lock().unwrap();
@@ -3,7 +3,9 @@ use std::task::Poll;
 use async_trait::async_trait;
 use futures_core::Stream;
 
-use super::{error::X11InputCaptureCreationError, Capture, CaptureError, CaptureEvent, Position};
+use super::{
+    error::X11InputCaptureCreationError, Capture, CaptureError, CaptureEvent, EdgeSpan, Position,
+};
 
 pub struct X11InputCapture {}
 
@@ -15,7 +17,7 @@ impl X11InputCapture {
 
 #[async_trait]
 impl Capture for X11InputCapture {
-    async fn create(&mut self, _pos: Position) -> Result<(), CaptureError> {
+    async fn create(&mut self, _pos: Position, _span: EdgeSpan) -> Result<(), CaptureError> {
         Ok(())
     }
 
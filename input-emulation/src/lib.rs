@@ -69,10 +69,29 @@ impl Display for Backend {
     }
 }
 
+/// emulation backends in order of preference
+/// (excluding the [`Backend::Dummy`] fallback)
+pub const BACKENDS: &[Backend] = &[
+    #[cfg(all(unix, feature = "wlroots", not(target_os = "macos")))]
+    Backend::Wlroots,
+    #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+    Backend::Libei,
+    #[cfg(all(unix, feature = "remote_desktop_portal", not(target_os = "macos")))]
+    Backend::Xdp,
+    #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
+    Backend::X11,
+    #[cfg(windows)]
+    Backend::Windows,
+    #[cfg(target_os = "macos")]
+    Backend::MacOs,
+];
+
 pub struct InputEmulation {
     emulation: Box<dyn Emulation>,
     handles: HashSet<EmulationHandle>,
     pressed_keys: HashMap<EmulationHandle, HashSet<u32>>,
+    /// locked modifiers and group last reported by each handle
+    locked_modifiers: HashMap<EmulationHandle, (u32, u32)>,
 }
 
 impl InputEmulation {
@@ -96,6 +115,7 @@ impl InputEmulation {
             emulation,
             handles: HashSet::new(),
             pressed_keys: HashMap::new(),
+            locked_modifiers: HashMap::new(),
         })
     }
 
@@ -108,21 +128,7 @@ impl InputEmulation {
             return b;
         }
 
-        for backend in [
-            #[cfg(all(unix, feature = "wlroots", not(target_os = "macos")))]
-            Backend::Wlroots,
-            #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
-            Backend::Libei,
-            #[cfg(all(unix, feature = "remote_desktop_portal", not(target_os = "macos")))]
-            Backend::Xdp,
-            #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
-            Backend::X11,
-            #[cfg(windows)]
-            Backend::Windows,
-            #[cfg(target_os = "macos")]
-            Backend::MacOs,
-            Backend::Dummy,
-        ] {
+        for backend in BACKENDS.iter().copied().chain([Backend::Dummy]) {
             match Self::with_backend(backend).await {
                 Ok(b) => {
                     log::info!("using emulation backend: {backend}");
@@ -136,6 +142,28 @@ impl InputEmulation {
         Err(EmulationCreationError::NoAvailableBackend)
     }
 
+    /// check whether the given backend is available,
+    /// the portal is only checked for the remote desktop interface
+    pub async fn probe(backend: Backend) -> Result<(), EmulationCreationError> {
+        #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+        if backend == Backend::Libei {
+            return Ok(libei::probe().await?);
+        }
+        #[cfg(all(unix, feature = "remote_desktop_portal", not(target_os = "macos")))]
+        if backend == Backend::Xdp {
+            return Ok(xdg_desktop_portal::probe().await?);
+        }
+        Self::with_backend(backend).await?.terminate().await;
+        Ok(())
+    }
+
+    /// whether the compositor advertises each of the globals
+    /// the wlroots backend requires
+    #[cfg(all(unix, feature = "wlroots", not(target_os = "macos")))]
+    pub fn wlroots_globals() -> Result<Vec<(&'static str, bool)>, EmulationCreationError> {
+        Ok(wlroots::probe_globals()?)
+    }
+
     pub async fn consume(
         &mut self,
         event: Event,
@@ -149,6 +177,11 @@ impl InputEmulation {
                 }
                 Ok(())
             }
+            Event::Keyboard(KeyboardEvent::Modifiers { locked, group, .. }) => {
+                // the lock state of the sender is authoritative
+                self.locked_modifiers.insert(handle, (locked, group));
+                self.emulation.consume(event, handle).await
+            }
             _ => self.emulation.consume(event, handle).await,
         }
     }
@@ -167,6 +200,7 @@ impl InputEmulation {
         let _ = self.release_keys(handle).await;
         if self.handles.remove(&handle) {
             self.pressed_keys.remove(&handle);
+            self.locked_modifiers.remove(&handle);
             self.emulation.destroy(handle).await
         }
     }
@@ -194,11 +228,18 @@ impl InputEmulation {
             }
         }
 
+        // only release depressed and latched modifiers,
+        // caps lock / num lock and the layout stay as they were
+        let (locked, group) = self
+            .locked_modifiers
+            .get(&handle)
+            .copied()
+            .unwrap_or_default();
         let event = Event::Keyboard(KeyboardEvent::Modifiers {
             depressed: 0,
             latched: 0,
-            locked: 0,
-            group: 0,
+            locked,
+            group,
         });
         self.emulation.consume(event, handle).await?;
         Ok(())
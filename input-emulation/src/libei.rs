@@ -74,6 +74,13 @@ async fn get_ei_fd<'a>(
     Ok((remote_desktop, session, fd))
 }
 
+/// check that the remote desktop portal is available without creating a session,
+/// which could ask the user for permission
+pub(crate) async fn probe() -> Result<(), LibeiEmulationCreationError> {
+    RemoteDesktop::new().await?;
+    Ok(())
+}
+
 impl<'a> LibeiEmulation<'a> {
     pub(crate) async fn new() -> Result<Self, LibeiEmulationCreationError> {
         let (_remote_desktop, session, eifd) = get_ei_fd().await?;
@@ -49,6 +49,27 @@ pub(crate) struct WlrootsEmulation {
     queue: EventQueue<State>,
 }
 
+/// globals input emulation depends on
+const REQUIRED_GLOBALS: [&str; 3] = [
+    "wl_seat",
+    "zwlr_virtual_pointer_manager_v1",
+    "zwp_virtual_keyboard_manager_v1",
+];
+
+/// whether the compositor advertises each of the globals required for emulation,
+/// without creating any virtual devices
+pub(crate) fn probe_globals() -> Result<Vec<(&'static str, bool)>, WlrootsEmulationCreationError> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, _queue) = registry_queue_init::<State>(&conn)?;
+    let advertised: Vec<String> = globals
+        .contents()
+        .with_list(|list| list.iter().map(|global| global.interface.clone()).collect());
+    Ok(REQUIRED_GLOBALS
+        .iter()
+        .map(|&name| (name, advertised.iter().any(|i| i == name)))
+        .collect())
+}
+
 impl WlrootsEmulation {
     pub(crate) fn new() -> Result<Self, WlrootsEmulationCreationError> {
         let conn = Connection::connect_to_env()?;
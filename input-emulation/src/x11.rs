@@ -6,7 +6,7 @@ use x11::{
 };
 
 use input_event::{
-    Event, KeyboardEvent, PointerEvent, BTN_BACK, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT,
+    Event, KeyboardEvent, PointerEvent, BTN_BACK, BTN_LEFT, BTN_MIDDLE, BTN_MOUSE_LAST, BTN_RIGHT,
 };
 
 use crate::error::EmulationError;
@@ -19,6 +19,19 @@ pub(crate) struct X11Emulation {
 
 unsafe impl Send for X11Emulation {}
 
+/// X button number of the linux button code `button`
+fn x11_button(button: u32) -> Option<u32> {
+    match button {
+        BTN_LEFT => Some(1),
+        BTN_MIDDLE => Some(2),
+        BTN_RIGHT => Some(3),
+        // 4 - 7 are scroll buttons, the remaining buttons follow
+        // in order (as mapped by the evdev and libinput X drivers)
+        BTN_BACK..=BTN_MOUSE_LAST => Some(button - BTN_BACK + 8),
+        _ => None,
+    }
+}
+
 impl X11Emulation {
     pub(crate) fn new() -> Result<Self, X11EmulationCreationError> {
         let display = unsafe {
@@ -39,15 +52,11 @@ impl X11Emulation {
     }
 
     fn emulate_mouse_button(&self, button: u32, state: u32) {
+        let Some(x11_button) = x11_button(button) else {
+            log::warn!("unsupported mouse button {button:#x}");
+            return;
+        };
         unsafe {
-            let x11_button = match button {
-                BTN_RIGHT => 3,
-                BTN_MIDDLE => 2,
-                BTN_BACK => 8,
-                BTN_FORWARD => 9,
-                BTN_LEFT => 1,
-                _ => 1,
-            };
             xtest::XTestFakeButtonEvent(self.display, x11_button, state as i32, 0);
         };
     }
@@ -152,3 +161,29 @@ impl Emulation for X11Emulation {
         /* nothing to do */
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_event::BTN_FORWARD;
+
+    #[test]
+    fn mouse_buttons() {
+        assert_eq!(x11_button(BTN_LEFT), Some(1));
+        assert_eq!(x11_button(BTN_MIDDLE), Some(2));
+        assert_eq!(x11_button(BTN_RIGHT), Some(3));
+        assert_eq!(x11_button(BTN_BACK), Some(8));
+        assert_eq!(x11_button(BTN_FORWARD), Some(9));
+
+        let mut seen = vec![];
+        for button in BTN_LEFT..=BTN_MOUSE_LAST {
+            let x11_button = x11_button(button).expect("mouse button is supported");
+            // 4 - 7 scroll
+            assert!(!(4..=7).contains(&x11_button), "{button:#x} scrolls");
+            assert!(!seen.contains(&x11_button), "{button:#x} is a duplicate");
+            seen.push(x11_button);
+        }
+        assert_eq!(x11_button(BTN_LEFT - 1), None);
+        assert_eq!(x11_button(BTN_MOUSE_LAST + 1), None);
+    }
+}
@@ -22,6 +22,13 @@ pub(crate) struct DesktopPortalEmulation<'a> {
     session: Session<'a, RemoteDesktop<'a>>,
 }
 
+/// check that the remote desktop portal is available without creating a session,
+/// which could ask the user for permission
+pub(crate) async fn probe() -> Result<(), XdpEmulationCreationError> {
+    RemoteDesktop::new().await?;
+    Ok(())
+}
+
 impl<'a> DesktopPortalEmulation<'a> {
     pub(crate) async fn new() -> Result<DesktopPortalEmulation<'a>, XdpEmulationCreationError> {
         log::debug!("connecting to org.freedesktop.portal.RemoteDesktop portal ...");
@@ -143,7 +150,6 @@ impl<'a> Emulation for DesktopPortalEmulation<'a> {
 
 impl<'a> AsyncDrop for DesktopPortalEmulation<'a> {
     #[doc = r" Perform the async cleanup."]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn async_drop<'async_trait>(
         self,
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 
 pub mod error;
@@ -10,10 +11,15 @@ mod libei;
 pub const BTN_LEFT: u32 = 0x110;
 pub const BTN_RIGHT: u32 = 0x111;
 pub const BTN_MIDDLE: u32 = 0x112;
+/// `BTN_SIDE`, the back button of most mice
 pub const BTN_BACK: u32 = 0x113;
+/// `BTN_EXTRA`, the forward button of most mice
 pub const BTN_FORWARD: u32 = 0x114;
+/// last linux mouse button code; buttons from `BTN_LEFT` up to this
+/// one are passed through as they are
+pub const BTN_MOUSE_LAST: u32 = 0x11f;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum PointerEvent {
     /// relative motion event
     Motion { time: u32, dx: f64, dy: f64 },
@@ -25,9 +31,11 @@ pub enum PointerEvent {
     AxisDiscrete120 { axis: u8, value: i32 },
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum KeyboardEvent {
-    /// a key press / release event
+    /// a key press / release event.
+    /// `key` is always a linux evdev keycode ([`scancode::Linux`]) without
+    /// the X11 offset of 8, backends convert from / to their native codes
     Key { time: u32, key: u32, state: u8 },
     /// modifiers changed state
     Modifiers {
@@ -38,7 +46,7 @@ pub enum KeyboardEvent {
     },
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Event {
     /// pointer event (motion / button / axis)
     Pointer(PointerEvent),
@@ -66,7 +74,7 @@ impl Display for PointerEvent {
                 if let Some(button) = str {
                     write!(f, "button({button}, {state})")
                 } else {
-                    write!(f, "button({button}, {state}")
+                    write!(f, "button({button}, {state})")
                 }
             }
             PointerEvent::Axis {
@@ -15,6 +15,7 @@ pub(super) enum CommandType {
     List,
     SetHost,
     SetPort,
+    DebugState,
 }
 
 #[derive(Debug)]
@@ -40,6 +41,7 @@ impl FromStr for CommandType {
             "list" => Ok(Self::List),
             "set-host" => Ok(Self::SetHost),
             "set-port" => Ok(Self::SetPort),
+            "debug-state" => Ok(Self::DebugState),
             "help" => Ok(Self::Help),
             _ => Err(InvalidCommand { cmd: s.to_string() }),
         }
@@ -57,6 +59,7 @@ pub(super) enum Command {
     List,
     SetHost(ClientHandle, String),
     SetPort(ClientHandle, Option<u16>),
+    DebugState,
 }
 
 impl CommandType {
@@ -71,6 +74,7 @@ impl CommandType {
             CommandType::List => "list",
             CommandType::SetHost => "set-host <id> <host>",
             CommandType::SetPort => "set-port <id> <host>",
+            CommandType::DebugState => "debug-state",
         }
     }
 }
@@ -108,6 +112,7 @@ impl FromStr for Command {
             CommandType::List => Ok(Command::List),
             CommandType::SetHost => parse_set_host(args),
             CommandType::SetPort => parse_set_port(args),
+            CommandType::DebugState => Ok(Command::DebugState),
         }
     }
 }
@@ -175,6 +175,16 @@ impl Cli {
                 self.tx.request(request).await?;
                 self.update_client(handle).await?;
             }
+            Command::DebugState => {
+                self.tx.request(FrontendRequest::DebugState).await?;
+                while let Some(e) = self.rx.next().await {
+                    let event = e?;
+                    self.handle_event(event.clone());
+                    if let FrontendEvent::DebugState(_) = event {
+                        break;
+                    }
+                }
+            }
             Command::Help => {
                 for cmd_type in [
                     CommandType::List,
@@ -184,6 +194,7 @@ impl Cli {
                     CommandType::Deactivate,
                     CommandType::SetHost,
                     CommandType::SetPort,
+                    CommandType::DebugState,
                 ] {
                     eprintln!("{}", cmd_type.usage());
                 }
@@ -285,6 +296,30 @@ impl Cli {
             }
             FrontendEvent::IncomingConnected(..) => {}
             FrontendEvent::IncomingDisconnected(..) => {}
+            FrontendEvent::CursorOwner(owner) => match owner {
+                Some(h) => eprintln!("cursor owner: client {h}"),
+                None => eprintln!("cursor owner: local"),
+            },
+            FrontendEvent::Paused(paused) => match paused {
+                true => eprintln!("input forwarding paused"),
+                false => eprintln!("input forwarding resumed"),
+            },
+            FrontendEvent::ClientConnected(h, addr) => {
+                eprintln!("client {h} connected @ {addr}");
+            }
+            FrontendEvent::ClientConnectionFailed(h, e) => {
+                eprintln!("client {h}: failed to connect: {e}");
+            }
+            FrontendEvent::ClientDisconnected(h, reason) => {
+                eprintln!("client {h} disconnected: {reason}");
+            }
+            FrontendEvent::ClientRtt(h, rtt) => {
+                eprintln!("client {h}: rtt {rtt:?}");
+            }
+            FrontendEvent::DebugState(state) => match serde_json::to_string_pretty(&state) {
+                Ok(json) => println!("{json}"),
+                Err(e) => eprintln!("{e}"),
+            },
         }
     }
 
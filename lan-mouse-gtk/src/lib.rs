@@ -144,6 +144,17 @@ fn build_ui(app: &Application) {
                     }
                     FrontendEvent::IncomingConnected(..) => {}
                     FrontendEvent::IncomingDisconnected(..) => {}
+                    FrontendEvent::CursorOwner(owner) => {
+                        window.set_cursor_owner(owner);
+                    }
+                    FrontendEvent::Paused(paused) => {
+                        window.set_paused(paused);
+                    }
+                    FrontendEvent::ClientConnected(..) => {}
+                    FrontendEvent::ClientConnectionFailed(..) => {}
+                    FrontendEvent::ClientDisconnected(..) => {}
+                    FrontendEvent::ClientRtt(..) => {}
+                    FrontendEvent::DebugState(..) => {}
                 }
             }
         }
@@ -426,4 +426,21 @@ impl Window {
     pub(crate) fn set_pk_fp(&self, fingerprint: &str) {
         self.imp().fingerprint_row.set_subtitle(fingerprint);
     }
+
+    pub(crate) fn set_cursor_owner(&self, owner: Option<ClientHandle>) {
+        let owner = match owner {
+            None => "local".to_string(),
+            Some(handle) => self
+                .client_idx(handle)
+                .and_then(|idx| self.client_by_idx(idx as u32))
+                .and_then(|c| c.get_data().hostname)
+                .unwrap_or(format!("client {handle}")),
+        };
+        self.imp().cursor_owner_row.set_subtitle(&owner);
+    }
+
+    pub(crate) fn set_paused(&self, paused: bool) {
+        let title = if paused { "cursor (paused)" } else { "cursor" };
+        self.imp().cursor_owner_row.set_title(title);
+    }
 }
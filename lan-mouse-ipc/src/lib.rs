@@ -5,6 +5,7 @@ use std::{
     io,
     net::{IpAddr, SocketAddr},
     str::FromStr,
+    time::Duration,
 };
 use thiserror::Error;
 
@@ -125,7 +126,7 @@ impl TryFrom<&str> for Position {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// hostname of this client
     pub hostname: Option<String>,
@@ -137,6 +138,46 @@ pub struct ClientConfig {
     pub pos: Position,
     /// enter hook
     pub cmd: Option<String>,
+    /// section of the edge (start and end fraction) that triggers a handoff,
+    /// the whole edge if `None`
+    pub edge_span: Option<(f64, f64)>,
+    /// invert horizontal pointer motion sent to this client
+    pub invert_x: bool,
+    /// invert vertical pointer motion sent to this client
+    pub invert_y: bool,
+    /// swap horizontal and vertical pointer motion (applied before inverting)
+    pub swap_axes: bool,
+    /// invert the scroll direction of this client
+    pub invert_scroll: bool,
+    /// kinds of input forwarded to this client
+    pub forward: InputFilter,
+    /// do not hand off the cursor while a local fullscreen window is focused
+    pub suppress_in_fullscreen: bool,
+    /// time (in ms) events are held back before they are sent to this client
+    pub send_delay_ms: u64,
+    /// local address the connection to this client is sent from,
+    /// chosen by the system if `None`
+    pub source_ip: Option<IpAddr>,
+}
+
+/// kinds of input that are forwarded to a client
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct InputFilter {
+    pub motion: bool,
+    pub buttons: bool,
+    pub scroll: bool,
+    pub keyboard: bool,
+}
+
+impl Default for InputFilter {
+    fn default() -> Self {
+        Self {
+            motion: true,
+            buttons: true,
+            scroll: true,
+            keyboard: true,
+        }
+    }
 }
 
 impl Default for ClientConfig {
@@ -147,6 +188,15 @@ impl Default for ClientConfig {
             fix_ips: Default::default(),
             pos: Default::default(),
             cmd: None,
+            edge_span: None,
+            invert_x: false,
+            invert_y: false,
+            swap_axes: false,
+            invert_scroll: false,
+            forward: Default::default(),
+            suppress_in_fullscreen: false,
+            send_delay_ms: 0,
+            source_ip: None,
         }
     }
 }
@@ -175,6 +225,27 @@ pub struct ClientState {
     pub resolving: bool,
 }
 
+/// internal state of the input capture, for debugging
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaptureDebugState {
+    /// client receiving input, `None` if the cursor is on this device
+    pub active_client: Option<ClientHandle>,
+    /// forwarding of captured input is paused
+    pub paused: bool,
+    /// sub-pixel motion left over from rounding to whole pixels
+    pub motion_remainder: (f64, f64),
+    /// accumulated motion that has not been sent yet
+    pub pending_motion: Option<(f64, f64)>,
+    /// number of events held back by the packet interval floor
+    pub deferred_events: usize,
+    /// pressed keys (linux key codes)
+    pub pressed_keys: Vec<u32>,
+    /// pressed mouse buttons (linux button codes)
+    pub pressed_buttons: Vec<u32>,
+    /// depressed modifiers (xkb modifier mask)
+    pub modifiers: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FrontendEvent {
     /// client state has changed, new state must be requested via [`FrontendRequest::GetState`]
@@ -205,6 +276,38 @@ pub enum FrontendEvent {
     IncomingConnected(String, SocketAddr, Position),
     /// incoming disconnected
     IncomingDisconnected(SocketAddr),
+    /// the client that currently receives input, `None` if the cursor is on this device
+    CursorOwner(Option<ClientHandle>),
+    /// forwarding of captured input is paused
+    Paused(bool),
+    /// connected to a client at the given address
+    ClientConnected(ClientHandle, SocketAddr),
+    /// connecting to a client failed
+    ClientConnectionFailed(ClientHandle, String),
+    /// the connection to a client ended
+    ClientDisconnected(ClientHandle, DisconnectReason),
+    /// the round trip time to a client changed, reported at most once per second
+    ClientRtt(ClientHandle, Duration),
+    /// internal state of the input capture
+    DebugState(CaptureDebugState),
+}
+
+/// reason a connection to a client ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectReason {
+    /// the client stopped responding
+    Timeout,
+    /// the connection failed or was closed
+    Error(String),
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::Timeout => write!(f, "timed out"),
+            DisconnectReason::Error(e) => write!(f, "{e}"),
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -241,6 +344,8 @@ pub enum FrontendRequest {
     AuthorizeKey(String, String),
     /// remove fingerprint (fingerprint)
     RemoveAuthorizedKey(String),
+    /// request the internal state of the input capture
+    DebugState,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
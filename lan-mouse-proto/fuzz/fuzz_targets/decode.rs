@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// decoding untrusted packets must never panic and
+// every decoded event must survive an encode / decode round trip
+fuzz_target!(|data: &[u8]| {
+    if let Ok((seq, event)) = lan_mouse_proto::decode_packet(data) {
+        let (buf, len) = lan_mouse_proto::encode_packet(seq, event);
+        let (seq2, event2) =
+            lan_mouse_proto::decode_packet(&buf[..len]).expect("re-encoded packet must decode");
+        assert_eq!(seq, seq2);
+        // ProtoEvent is not PartialEq and may contain NaN
+        assert_eq!(format!("{event:?}"), format!("{event2:?}"));
+    }
+});
@@ -12,6 +12,21 @@ use thiserror::Error;
 /// type: u8, time: u32, dx: f64, dy: f64
 pub const MAX_EVENT_SIZE: usize = size_of::<u8>() + size_of::<u32>() + 2 * size_of::<f64>();
 
+/// version of the packet format, bumped on every incompatible change
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// first byte of every packet. The high bit is set, so it can not be
+/// mistaken for the event id at the start of packets of lan-mouse
+/// versions from before packets were versioned.
+const VERSION_BYTE: u8 = 0x80 | PROTOCOL_VERSION;
+
+/// size of the header preceding every event on the wire:
+/// version byte and sequence number
+const HEADER_SIZE: usize = size_of::<u8>() + size_of::<u32>();
+
+/// maximum size of a packet: header followed by the encoded event
+pub const MAX_PACKET_SIZE: usize = HEADER_SIZE + MAX_EVENT_SIZE;
+
 /// error type for protocol violations
 #[derive(Debug, Error)]
 pub enum ProtocolError {
@@ -19,12 +34,21 @@ pub enum ProtocolError {
     #[error("invalid event id: `{0}`")]
     InvalidEventId(#[from] TryFromPrimitiveError<EventType>),
     /// position type does not exist
-    #[error("invalid event id: `{0}`")]
+    #[error("invalid position: `{0}`")]
     InvalidPosition(#[from] TryFromPrimitiveError<Position>),
+    /// data ended before the event was complete
+    #[error("event truncated")]
+    Truncated,
+    /// data is longer than the encoded event
+    #[error("invalid event length: `{0}`")]
+    InvalidLength(usize),
+    /// packet was sent by an incompatible version of lan-mouse
+    #[error("unsupported protocol version (first byte `{0:#04x}`), both devices need to run a compatible version of lan-mouse")]
+    UnsupportedVersion(u8),
 }
 
 /// Position of a client
-#[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum Position {
     Left,
@@ -46,7 +70,7 @@ impl Display for Position {
 }
 
 /// main lan-mouse protocol event type
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ProtoEvent {
     /// notify a client that the cursor entered its region at the given position
     /// [`ProtoEvent::Ack`] with the same serial is used for synchronization between devices
@@ -128,53 +152,75 @@ impl TryFrom<[u8; MAX_EVENT_SIZE]> for ProtoEvent {
     type Error = ProtocolError;
 
     fn try_from(buf: [u8; MAX_EVENT_SIZE]) -> Result<Self, Self::Error> {
-        let mut buf = &buf[..];
-        let event_type = decode_u8(&mut buf)?;
-        match EventType::try_from(event_type)? {
-            EventType::PointerMotion => {
-                Ok(Self::Input(InputEvent::Pointer(PointerEvent::Motion {
-                    time: decode_u32(&mut buf)?,
-                    dx: decode_f64(&mut buf)?,
-                    dy: decode_f64(&mut buf)?,
-                })))
-            }
-            EventType::PointerButton => {
-                Ok(Self::Input(InputEvent::Pointer(PointerEvent::Button {
-                    time: decode_u32(&mut buf)?,
-                    button: decode_u32(&mut buf)?,
-                    state: decode_u32(&mut buf)?,
-                })))
-            }
-            EventType::PointerAxis => Ok(Self::Input(InputEvent::Pointer(PointerEvent::Axis {
-                time: decode_u32(&mut buf)?,
-                axis: decode_u8(&mut buf)?,
-                value: decode_f64(&mut buf)?,
-            }))),
-            EventType::PointerAxisValue120 => Ok(Self::Input(InputEvent::Pointer(
-                PointerEvent::AxisDiscrete120 {
-                    axis: decode_u8(&mut buf)?,
-                    value: decode_i32(&mut buf)?,
-                },
-            ))),
-            EventType::KeyboardKey => Ok(Self::Input(InputEvent::Keyboard(KeyboardEvent::Key {
-                time: decode_u32(&mut buf)?,
-                key: decode_u32(&mut buf)?,
-                state: decode_u8(&mut buf)?,
-            }))),
-            EventType::KeyboardModifiers => Ok(Self::Input(InputEvent::Keyboard(
-                KeyboardEvent::Modifiers {
-                    depressed: decode_u32(&mut buf)?,
-                    latched: decode_u32(&mut buf)?,
-                    locked: decode_u32(&mut buf)?,
-                    group: decode_u32(&mut buf)?,
-                },
-            ))),
-            EventType::Ping => Ok(Self::Ping),
-            EventType::Pong => Ok(Self::Pong(decode_u8(&mut buf)? != 0)),
-            EventType::Enter => Ok(Self::Enter(decode_u8(&mut buf)?.try_into()?)),
-            EventType::Leave => Ok(Self::Leave(decode_u32(&mut buf)?)),
-            EventType::Ack => Ok(Self::Ack(decode_u32(&mut buf)?)),
+        decode_event(&mut &buf[..])
+    }
+}
+
+impl TryFrom<&[u8]> for ProtoEvent {
+    type Error = ProtocolError;
+
+    /// decode an event that takes up exactly the given bytes
+    fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() > MAX_EVENT_SIZE {
+            return Err(ProtocolError::InvalidLength(buf.len()));
         }
+        let mut data = buf;
+        let event = decode_event(&mut data)?;
+        if !data.is_empty() {
+            return Err(ProtocolError::InvalidLength(buf.len()));
+        }
+        Ok(event)
+    }
+}
+
+fn decode_event(buf: &mut &[u8]) -> Result<ProtoEvent, ProtocolError> {
+    let event_type = decode_u8(buf)?;
+    match EventType::try_from(event_type)? {
+        EventType::PointerMotion => Ok(ProtoEvent::Input(InputEvent::Pointer(
+            PointerEvent::Motion {
+                time: decode_u32(buf)?,
+                dx: decode_f64(buf)?,
+                dy: decode_f64(buf)?,
+            },
+        ))),
+        EventType::PointerButton => Ok(ProtoEvent::Input(InputEvent::Pointer(
+            PointerEvent::Button {
+                time: decode_u32(buf)?,
+                button: decode_u32(buf)?,
+                state: decode_u32(buf)?,
+            },
+        ))),
+        EventType::PointerAxis => Ok(ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Axis {
+            time: decode_u32(buf)?,
+            axis: decode_u8(buf)?,
+            value: decode_f64(buf)?,
+        }))),
+        EventType::PointerAxisValue120 => Ok(ProtoEvent::Input(InputEvent::Pointer(
+            PointerEvent::AxisDiscrete120 {
+                axis: decode_u8(buf)?,
+                value: decode_i32(buf)?,
+            },
+        ))),
+        EventType::KeyboardKey => Ok(ProtoEvent::Input(InputEvent::Keyboard(
+            KeyboardEvent::Key {
+                time: decode_u32(buf)?,
+                key: decode_u32(buf)?,
+                state: decode_u8(buf)?,
+            },
+        ))),
+        EventType::KeyboardModifiers => Ok(ProtoEvent::Input(InputEvent::Keyboard(
+            KeyboardEvent::Modifiers {
+                depressed: decode_u32(buf)?,
+                latched: decode_u32(buf)?,
+                locked: decode_u32(buf)?,
+                group: decode_u32(buf)?,
+            },
+        ))),
+        EventType::Ping => Ok(ProtoEvent::Ping),
+        EventType::Pong => Ok(ProtoEvent::Pong(decode_u8(buf)? != 0)),
+        EventType::Enter => Ok(ProtoEvent::Enter(decode_u8(buf)?.try_into()?)),
+        EventType::Leave => Ok(ProtoEvent::Leave(decode_u32(buf)?)),
+        EventType::Ack => Ok(ProtoEvent::Ack(decode_u32(buf)?)),
     }
 }
 
@@ -244,13 +290,36 @@ impl From<ProtoEvent> for ([u8; MAX_EVENT_SIZE], usize) {
     }
 }
 
+/// encode an event preceded by the protocol version and its sequence number
+pub fn encode_packet(seq: u32, event: ProtoEvent) -> ([u8; MAX_PACKET_SIZE], usize) {
+    let (event_buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    buf[0] = VERSION_BYTE;
+    buf[1..HEADER_SIZE].copy_from_slice(&seq.to_be_bytes());
+    buf[HEADER_SIZE..HEADER_SIZE + len].copy_from_slice(&event_buf[..len]);
+    (buf, HEADER_SIZE + len)
+}
+
+/// decode a packet into its sequence number and event
+pub fn decode_packet(buf: &[u8]) -> Result<(u32, ProtoEvent), ProtocolError> {
+    let mut data = buf;
+    let version = decode_u8(&mut data)?;
+    if version != VERSION_BYTE {
+        return Err(ProtocolError::UnsupportedVersion(version));
+    }
+    let seq = decode_u32(&mut data)?;
+    Ok((seq, data.try_into()?))
+}
+
 macro_rules! decode_impl {
     ($t:ty) => {
         paste! {
             fn [<decode_ $t>](data: &mut &[u8]) -> Result<$t, ProtocolError> {
-                let (int_bytes, rest) = data.split_at(size_of::<$t>());
+                let (int_bytes, rest) = data
+                    .split_first_chunk::<{ size_of::<$t>() }>()
+                    .ok_or(ProtocolError::Truncated)?;
                 *data = rest;
-                Ok($t::from_be_bytes(int_bytes.try_into().unwrap()))
+                Ok($t::from_be_bytes(*int_bytes))
             }
         }
     };
@@ -280,3 +349,158 @@ encode_impl!(u8);
 encode_impl!(u32);
 encode_impl!(i32);
 encode_impl!(f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(event: ProtoEvent) -> Vec<u8> {
+        let (buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
+        buf[..len].to_vec()
+    }
+
+    fn events() -> Vec<ProtoEvent> {
+        vec![
+            ProtoEvent::Enter(Position::Bottom),
+            ProtoEvent::Leave(7),
+            ProtoEvent::Ack(7),
+            ProtoEvent::Ping,
+            ProtoEvent::Pong(true),
+            ProtoEvent::Pong(false),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Motion {
+                time: 1,
+                dx: 1.5,
+                dy: -2.25,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Button {
+                time: 2,
+                button: 0x110,
+                state: 1,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Axis {
+                time: 3,
+                axis: 1,
+                value: -15.0,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: 0,
+                value: -120,
+            })),
+            ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Key {
+                time: 4,
+                key: 30,
+                state: 0,
+            })),
+            ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Modifiers {
+                depressed: 1,
+                latched: 2,
+                locked: 16,
+                group: 0,
+            })),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for event in events() {
+            let buf = encode(event);
+            assert_eq!(ProtoEvent::try_from(&buf[..]).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn esc_round_trip() {
+        // key events carry evdev keycodes, 1 is KEY_ESC
+        let esc = ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key: 1,
+            state: 1,
+        }));
+        let (buf, len) = encode_packet(0, esc);
+        let (_, decoded) = decode_packet(&buf[..len]).unwrap();
+        let ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Key { key, .. })) = decoded
+        else {
+            panic!("decoded {decoded}");
+        };
+        assert_eq!(
+            input_event::scancode::Linux::try_from(key),
+            Ok(input_event::scancode::Linux::KeyEsc)
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        for event in events() {
+            let buf = encode(event);
+            for len in 0..buf.len() {
+                assert!(
+                    matches!(
+                        ProtoEvent::try_from(&buf[..len]),
+                        Err(ProtocolError::Truncated)
+                    ),
+                    "{event} truncated to {len} bytes"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn over_long() {
+        for event in events() {
+            let mut buf = encode(event);
+            buf.push(0);
+            assert!(matches!(
+                ProtoEvent::try_from(&buf[..]),
+                Err(ProtocolError::InvalidLength(_))
+            ));
+        }
+        let buf = [0u8; MAX_EVENT_SIZE + 1];
+        assert!(matches!(
+            ProtoEvent::try_from(&buf[..]),
+            Err(ProtocolError::InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        for (seq, event) in events().into_iter().enumerate() {
+            let seq = u32::MAX - seq as u32;
+            let (buf, len) = encode_packet(seq, event);
+            assert_eq!(decode_packet(&buf[..len]).unwrap(), (seq, event));
+        }
+    }
+
+    #[test]
+    fn unversioned_packet() {
+        // unversioned packets start with the event id
+        let buf = encode(ProtoEvent::Ping);
+        assert!(matches!(
+            decode_packet(&buf),
+            Err(ProtocolError::UnsupportedVersion(b)) if b == EventType::Ping as u8
+        ));
+        let (mut buf, len) = encode_packet(0, ProtoEvent::Ping);
+        buf[0] = 0x80 | (PROTOCOL_VERSION + 1);
+        assert!(matches!(
+            decode_packet(&buf[..len]),
+            Err(ProtocolError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_event_id() {
+        let buf = [EventType::Ack as u8 + 1, 0, 0, 0, 0];
+        assert!(matches!(
+            ProtoEvent::try_from(&buf[..]),
+            Err(ProtocolError::InvalidEventId(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_position() {
+        let buf = [EventType::Enter as u8, Position::Bottom as u8 + 1];
+        assert!(matches!(
+            ProtoEvent::try_from(&buf[..]),
+            Err(ProtocolError::InvalidPosition(_))
+        ));
+    }
+}
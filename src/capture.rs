@@ -6,15 +6,26 @@ use std::{
 
 use futures::StreamExt;
 use input_capture::{
-    CaptureError, CaptureEvent, CaptureHandle, InputCapture, InputCaptureError, Position,
+    CaptureError, CaptureEvent, CaptureHandle, CornerHandoff, CursorReturn, EdgeSpan, InputCapture,
+    InputCaptureError, LockLost, Position,
 };
-use input_event::scancode;
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
+use lan_mouse_ipc::CaptureDebugState;
 use lan_mouse_proto::ProtoEvent;
 use local_channel::mpsc::{channel, Receiver, Sender};
-use tokio::task::{spawn_local, JoinHandle};
+use tokio::{
+    task::{spawn_local, JoinHandle},
+    time::{self, MissedTickBehavior},
+};
 use tokio_util::sync::CancellationToken;
 
-use crate::connect::LanMouseConnection;
+use crate::{
+    client::ClientManager, config::Config, connect::LanMouseConnection, transform::EventTransformer,
+};
+
+/// time to wait for the modifier state after a crossing, before
+/// giving up and releasing the capture
+const CROSSING_TIMEOUT: Duration = Duration::from_millis(100);
 
 pub(crate) struct Capture {
     cancellation_token: CancellationToken,
@@ -37,6 +48,13 @@ pub(crate) enum ICaptureEvent {
     /// either the remote client leaving its device region,
     /// a new device entering the screen or the release bind.
     ClientEntered(u64),
+    /// the client receiving input changed,
+    /// `None` if the cursor is back on this device
+    CursorOwner(Option<CaptureHandle>),
+    /// forwarding of captured input was paused / resumed
+    Paused(bool),
+    /// internal state, as requested by [`Capture::request_debug_state`]
+    DebugState(CaptureDebugState),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -54,32 +72,61 @@ enum CaptureRequest {
     /// capture must release the mouse
     Release,
     /// add a capture client
-    Create(CaptureHandle, Position, CaptureType),
+    Create(CaptureHandle, Position, EdgeSpan, CaptureType),
     /// destory a capture client
     Destroy(CaptureHandle),
     /// reenable input capture
     Reenable,
+    /// report the internal state
+    DebugState,
 }
 
 impl Capture {
     pub(crate) fn new(
-        backend: Option<input_capture::Backend>,
+        config: &Config,
         conn: LanMouseConnection,
-        release_bind: Vec<scancode::Linux>,
+        client_manager: ClientManager,
+        transformers: Vec<Box<dyn EventTransformer>>,
     ) -> Self {
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let cancellation_token = CancellationToken::new();
         let capture_task = CaptureTask {
             active_client: None,
-            backend,
+            backend: config.capture_backend.map(|b| b.into()),
+            backend_switch_bind: config.backend_switch_bind.clone(),
             cancellation_token: cancellation_token.clone(),
             captures: Default::default(),
+            client_manager,
             conn,
+            crossing_modifiers: config.crossing_modifiers,
+            crossing_pending: None,
+            cursor_owner: None,
+            cursor_return: config.cursor_return.into(),
+            arming_delay: config.arming_delay,
+            lock_lost: config.lock_lost.into(),
+            corner_handoff: config.corner_handoff.into(),
             event_tx,
+            transformers,
+            motion_interval: config.motion_interval,
+            adaptive_motion_interval: config.adaptive_motion_interval,
+            integer_motion: config.integer_motion,
+            motion_rounding: Default::default(),
+            min_packet_interval: config.min_packet_interval,
+            handoff_ramp: config.handoff_ramp,
+            handoff: None,
+            last_packet: None,
+            deferred: vec![],
+            packet_stats: Default::default(),
+            pause_bind: config.pause_bind.clone(),
+            reachability_timeout: config.reachability_timeout,
+            pause_bind_pressed: false,
+            paused: false,
+            pending_motion: None,
             request_rx,
-            release_bind: Rc::new(RefCell::new(release_bind)),
+            release_bind: Rc::new(RefCell::new(config.release_bind.clone())),
             state: Default::default(),
+            switched_from: None,
         };
         let task = spawn_local(capture_task.run());
         Self {
@@ -108,11 +155,15 @@ impl Capture {
         &self,
         handle: CaptureHandle,
         pos: lan_mouse_ipc::Position,
+        span: Option<(f64, f64)>,
         capture_type: CaptureType,
     ) {
         let pos = to_capture_pos(pos);
+        let span = span
+            .map(|(start, end)| EdgeSpan::new(start, end))
+            .unwrap_or_default();
         self.request_tx
-            .send(CaptureRequest::Create(handle, pos, capture_type))
+            .send(CaptureRequest::Create(handle, pos, span, capture_type))
             .expect("channel closed");
     }
 
@@ -128,6 +179,13 @@ impl Capture {
             .expect("channel closed");
     }
 
+    /// request the internal state, reported as [`ICaptureEvent::DebugState`]
+    pub(crate) fn request_debug_state(&self) {
+        self.request_tx
+            .send(CaptureRequest::DebugState)
+            .expect("channel closed");
+    }
+
     pub(crate) async fn event(&mut self) -> ICaptureEvent {
         self.event_rx.recv().await.expect("channel closed")
     }
@@ -153,18 +211,88 @@ macro_rules! debounce {
 struct CaptureTask {
     active_client: Option<CaptureHandle>,
     backend: Option<input_capture::Backend>,
+    backend_switch_bind: Vec<scancode::Linux>,
     cancellation_token: CancellationToken,
-    captures: Vec<(CaptureHandle, Position, CaptureType)>,
+    captures: Vec<(CaptureHandle, Position, EdgeSpan, CaptureType)>,
+    client_manager: ClientManager,
     conn: LanMouseConnection,
+    /// modifier mask that has to be held to hand off the cursor, 0 if disabled
+    crossing_modifiers: u32,
+    /// capture waiting for the modifier state to decide the crossing
+    /// and the time the capture began
+    crossing_pending: Option<(CaptureHandle, time::Instant)>,
+    cursor_owner: Option<CaptureHandle>,
+    cursor_return: CursorReturn,
+    /// time the pointer has to rest at the edge before it is captured
+    arming_delay: Duration,
+    /// what happens when the compositor breaks the pointer lock
+    lock_lost: LockLost,
+    /// client the cursor is handed off to in a corner
+    corner_handoff: CornerHandoff,
     event_tx: Sender<ICaptureEvent>,
+    /// applied to captured input before sending
+    transformers: Vec<Box<dyn EventTransformer>>,
+    motion_interval: Option<Duration>,
+    /// bounds of the motion interval, adapted to the round trip time
+    adaptive_motion_interval: Option<(Duration, Duration)>,
+    /// round motion to whole pixels before sending
+    integer_motion: bool,
+    /// sub-pixel motion left over from rounding
+    motion_rounding: MotionRounding,
+    /// minimum time between two sent packets
+    min_packet_interval: Option<Duration>,
+    /// duration of the sensitivity ramp after a handoff and the scale it starts at
+    handoff_ramp: Option<(Duration, f64)>,
+    /// time of the last handoff
+    handoff: Option<Instant>,
+    /// time the last packet was sent
+    last_packet: Option<time::Instant>,
+    /// events held back by the packet interval floor
+    deferred: Vec<ProtoEvent>,
+    /// events merged or deferred because of the packet interval floor
+    packet_stats: PacketStats,
+    pause_bind: Vec<scancode::Linux>,
+    pause_bind_pressed: bool,
+    paused: bool,
+    pending_motion: Option<(u32, f64, f64)>,
+    /// time within which the client must have answered a ping to enter it
+    reachability_timeout: Option<Duration>,
     release_bind: Rc<RefCell<Vec<scancode::Linux>>>,
     request_rx: Receiver<CaptureRequest>,
     state: State,
+    /// backend switched away from, until the next backend is created
+    switched_from: Option<input_capture::Backend>,
 }
 
 impl CaptureTask {
-    fn add_capture(&mut self, handle: CaptureHandle, pos: Position, capture_type: CaptureType) {
-        self.captures.push((handle, pos, capture_type));
+    fn add_capture(
+        &mut self,
+        handle: CaptureHandle,
+        pos: Position,
+        span: EdgeSpan,
+        capture_type: CaptureType,
+    ) -> (EdgeSpan, Vec<CaptureHandle>) {
+        // enter-only captures share the span of the default capture at their position
+        let mut respanned = vec![];
+        let span = match capture_type {
+            CaptureType::Default => {
+                for (h, p, s, t) in self.captures.iter_mut() {
+                    if *p == pos && *t == CaptureType::EnterOnly && *s != span {
+                        *s = span;
+                        respanned.push(*h);
+                    }
+                }
+                span
+            }
+            CaptureType::EnterOnly => self
+                .captures
+                .iter()
+                .find(|&&(_, p, _, t)| p == pos && t == CaptureType::Default)
+                .map(|&(_, _, s, _)| s)
+                .unwrap_or(span),
+        };
+        self.captures.push((handle, pos, span, capture_type));
+        (span, respanned)
     }
 
     fn remove_capture(&mut self, handle: CaptureHandle) {
@@ -174,7 +302,7 @@ impl CaptureTask {
     fn is_default_capture_at(&self, pos: Position) -> bool {
         self.captures
             .iter()
-            .any(|&(_, p, t)| p == pos && t == CaptureType::Default)
+            .any(|&(_, p, _, t)| p == pos && t == CaptureType::Default)
     }
 
     fn get_pos(&self, handle: CaptureHandle) -> Position {
@@ -190,7 +318,54 @@ impl CaptureTask {
             .iter()
             .find(|(h, ..)| *h == handle)
             .expect("no such capture")
-            .2
+            .3
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if self.paused != paused {
+            self.paused = paused;
+            self.pending_motion.take();
+            self.event_tx
+                .send(ICaptureEvent::Paused(paused))
+                .expect("channel closed");
+        }
+    }
+
+    /// report the internal state, `capture` is `None` while capture is disabled
+    fn report_debug_state(&self, capture: Option<&InputCapture>) {
+        let mut pressed_keys: Vec<u32> = capture
+            .into_iter()
+            .flat_map(|c| c.pressed_keys())
+            .map(|k| k as u32)
+            .collect();
+        pressed_keys.sort_unstable();
+        let mut pressed_buttons: Vec<u32> = capture
+            .into_iter()
+            .flat_map(|c| c.pressed_buttons())
+            .collect();
+        pressed_buttons.sort_unstable();
+        let state = CaptureDebugState {
+            active_client: self.active_client,
+            paused: self.paused,
+            motion_remainder: self.motion_rounding.remainder,
+            pending_motion: self.pending_motion.map(|(_, dx, dy)| (dx, dy)),
+            deferred_events: self.deferred.len(),
+            pressed_keys,
+            pressed_buttons,
+            modifiers: capture.map(|c| c.modifiers()).unwrap_or_default(),
+        };
+        self.event_tx
+            .send(ICaptureEvent::DebugState(state))
+            .expect("channel closed");
+    }
+
+    fn set_cursor_owner(&mut self, owner: Option<CaptureHandle>) {
+        if self.cursor_owner != owner {
+            self.cursor_owner = owner;
+            self.event_tx
+                .send(ICaptureEvent::CursorOwner(owner))
+                .expect("channel closed");
+        }
     }
 
     async fn run(mut self) {
@@ -198,13 +373,31 @@ impl CaptureTask {
             if let Err(e) = self.do_capture().await {
                 log::warn!("input capture exited: {e}");
             }
+            if let Some(previous) = self.switched_from {
+                // move on to the next backend, skipping those that fail to create
+                let current = self.backend.unwrap_or(previous);
+                let next = current.next();
+                if next == previous {
+                    log::warn!("no other capture backend available, staying on {previous}");
+                    self.switched_from = None;
+                } else if current == previous {
+                    log::info!("switching capture backend to {next}");
+                } else {
+                    log::info!("{current} capture not available, switching to {next}");
+                }
+                self.backend = Some(next);
+                continue;
+            }
             loop {
                 tokio::select! {
                     r = self.request_rx.recv() => match r.expect("channel closed") {
                         CaptureRequest::Reenable => break,
-                        CaptureRequest::Create(h, p, t) => self.add_capture(h, p, t),
+                        CaptureRequest::Create(h, p, s, t) => {
+                            self.add_capture(h, p, s, t);
+                        }
                         CaptureRequest::Destroy(h) => self.remove_capture(h),
                         CaptureRequest::Release => { /* nothing to do */ }
+                        CaptureRequest::DebugState => self.report_debug_state(None),
                     },
                     _ = self.cancellation_token.cancelled() => return,
                 }
@@ -218,6 +411,17 @@ impl CaptureTask {
             r = InputCapture::new(self.backend) => r?,
             _ = self.cancellation_token.cancelled() => return Ok(()),
         };
+        self.switched_from = None;
+        capture.set_cursor_return(self.cursor_return);
+        capture.set_arming_delay(self.arming_delay);
+        capture.set_lock_lost(self.lock_lost);
+        capture.set_corner_handoff(self.corner_handoff);
+        if self.crossing_modifiers != 0 && !capture.reports_modifiers() {
+            log::warn!(
+                "{} capture does not report modifiers, crossing_modifiers is ignored",
+                capture.backend()
+            );
+        }
 
         let _capture_guard = DropGuard::new(
             self.event_tx.clone(),
@@ -233,6 +437,7 @@ impl CaptureTask {
         }
 
         let r = self.do_capture_session(&mut capture).await;
+        self.set_cursor_owner(None);
 
         // FIXME replace with async drop when stabilized
         capture.terminate().await?;
@@ -242,9 +447,9 @@ impl CaptureTask {
 
     async fn create_captures(&mut self, capture: &mut InputCapture) -> Result<(), CaptureError> {
         let captures = self.captures.clone();
-        for (handle, pos, _type) in captures {
+        for (handle, pos, span, _type) in captures {
             tokio::select! {
-                r = capture.create(handle, pos) => r?,
+                r = create_capture(capture, handle, pos, span) => r?,
                 _ = self.cancellation_token.cancelled() => return Ok(()),
             }
         }
@@ -255,12 +460,37 @@ impl CaptureTask {
         &mut self,
         capture: &mut InputCapture,
     ) -> Result<(), InputCaptureError> {
+        // only polled while there is pending motion, i.e. if smoothing is enabled
+        let mut interval = self.motion_interval().unwrap_or(Duration::from_secs(1));
+        let mut motion_tick = time::interval(interval);
+        motion_tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
             tokio::select! {
                 event = capture.next() => match event {
                     Some(event) => self.handle_capture_event(capture, event?).await?,
                     None => return Ok(()),
                 },
+                _ = motion_tick.tick(), if self.pending_motion.is_some() => {
+                    // the floor wins over the tick, the motion is sent with the next tick
+                    if !self.rate_limited() {
+                        self.flush_motion(capture).await?;
+                    }
+                    // follow changes of the round trip time
+                    if let Some(i) = self.motion_interval().filter(|&i| i != interval) {
+                        interval = i;
+                        motion_tick = time::interval_at(time::Instant::now() + i, i);
+                        motion_tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    }
+                }
+                _ = time::sleep_until(self.next_packet()), if self.rate_limit_pending() => {
+                    self.flush_motion(capture).await?;
+                }
+                // e.g. a seat without a keyboard
+                _ = time::sleep_until(self.crossing_deadline()), if self.crossing_pending.is_some() => {
+                    self.crossing_pending.take();
+                    log::info!("releasing capture: modifier state not reported");
+                    self.release_capture(capture).await?;
+                }
                 (handle, event) = self.conn.recv() => {
                     if let Some(active) = self.active_client {
                         if handle != active {
@@ -287,9 +517,17 @@ impl CaptureTask {
                 e = self.request_rx.recv() => match e.expect("channel closed") {
                     CaptureRequest::Reenable => { /* already active */ },
                     CaptureRequest::Release => self.release_capture(capture).await?,
-                    CaptureRequest::Create(h, p, t) => {
-                        self.add_capture(h, p, t);
-                        capture.create(h, p).await?;
+                    CaptureRequest::DebugState => self.report_debug_state(Some(capture)),
+                    CaptureRequest::Create(h, p, s, t) => {
+                        let (s, respanned) = self.add_capture(h, p, s, t);
+                        // enter-only captures follow the span of the new client
+                        for &e in &respanned {
+                            capture.destroy(e).await?;
+                        }
+                        create_capture(capture, h, p, s).await?;
+                        for &e in &respanned {
+                            create_capture(capture, e, p, s).await?;
+                        }
                     }
                     CaptureRequest::Destroy(h) => {
                         self.remove_capture(h);
@@ -298,6 +536,9 @@ impl CaptureTask {
                 },
                 _ = self.cancellation_token.cancelled() => break,
             }
+            if self.switched_from.is_some() {
+                break;
+            }
         }
         Ok(())
     }
@@ -310,11 +551,70 @@ impl CaptureTask {
         let (handle, event) = event;
         log::trace!("({handle}): {event:?}");
 
+        // let the client release its pressed keys
+        if event == CaptureEvent::Lost {
+            log::info!("releasing capture: pointer lock broken by the compositor");
+            if let Some(active) = self.active_client {
+                self.send(capture, ProtoEvent::Leave(0), active).await?;
+            }
+            return self.release_capture(capture).await;
+        }
+
         if capture.keys_pressed(&self.release_bind.borrow()) {
             log::info!("releasing capture: release-bind pressed");
             return self.release_capture(capture).await;
         }
 
+        if !self.backend_switch_bind.is_empty() && capture.keys_pressed(&self.backend_switch_bind) {
+            log::info!("releasing capture: switching capture backend");
+            // the modifiers of the bind were already sent
+            self.release_pressed(capture, handle).await?;
+            // the next backend is picked once this one is terminated
+            self.switched_from = Some(capture.backend());
+            self.backend = Some(capture.backend());
+            return self.release_capture(capture).await;
+        }
+
+        // toggle pause when the bind is pressed, not while it is held
+        let pause_pressed = !self.pause_bind.is_empty() && capture.keys_pressed(&self.pause_bind);
+        let toggled = pause_pressed && !self.pause_bind_pressed;
+        self.pause_bind_pressed = pause_pressed;
+        if toggled {
+            self.set_paused(!self.paused);
+            if self.paused {
+                self.release_pressed(capture, handle).await?;
+            }
+            // the key completing the bind is not forwarded
+            return Ok(());
+        }
+
+        // the modifier state is reported right after the capture begins
+        if event == CaptureEvent::Begin
+            && self.crossing_modifiers != 0
+            && capture.reports_modifiers()
+        {
+            self.crossing_pending = Some((handle, time::Instant::now()));
+            return Ok(());
+        }
+        let (handle, event) = match self.crossing_pending {
+            Some((pending, _)) => match event {
+                CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Modifiers {
+                    depressed,
+                    ..
+                })) => {
+                    self.crossing_pending.take();
+                    if depressed & self.crossing_modifiers != self.crossing_modifiers {
+                        log::info!("releasing capture: crossing modifiers not held");
+                        return self.release_capture(capture).await;
+                    }
+                    (pending, CaptureEvent::Begin)
+                }
+                // input is discarded until the crossing is decided
+                _ => return Ok(()),
+            },
+            None => (handle, event),
+        };
+
         if event == CaptureEvent::Begin {
             self.event_tx
                 .send(ICaptureEvent::CaptureBegin(handle))
@@ -333,6 +633,28 @@ impl CaptureTask {
             return Ok(());
         }
 
+        // do not steal the cursor from a fullscreen window, e.g. a game
+        if event == CaptureEvent::Begin
+            && capture.fullscreen_active()
+            && self.client_manager.suppresses_in_fullscreen(handle)
+        {
+            log::info!(
+                "releasing capture: fullscreen window focused, not entering client {handle}"
+            );
+            return self.release_capture(capture).await;
+        }
+
+        // do not trap the cursor on a client that does not respond
+        if event == CaptureEvent::Begin {
+            if let Some(timeout) = self.reachability_timeout {
+                if !self.conn.responded_within(handle, timeout) {
+                    log::warn!("releasing capture: client {handle} is not reachable");
+                    self.conn.connect(handle).await;
+                    return self.release_capture(capture).await;
+                }
+            }
+        }
+
         // activated a new client
         if event == CaptureEvent::Begin && Some(handle) != self.active_client {
             self.state = State::WaitingForAck;
@@ -342,20 +664,240 @@ impl CaptureTask {
                 .expect("channel closed");
         }
 
+        if event == CaptureEvent::Begin {
+            self.set_cursor_owner(Some(handle));
+            self.handoff = Some(Instant::now());
+        }
+
+        // input stays captured but is not sent while paused
+        if self.paused && matches!(event, CaptureEvent::Input(_)) {
+            return Ok(());
+        }
+
         let opposite_pos = to_proto_pos(self.get_pos(handle).opposite());
+        let begin = event == CaptureEvent::Begin;
 
-        let event = match event {
-            CaptureEvent::Begin => ProtoEvent::Enter(opposite_pos),
+        let events = match event {
+            CaptureEvent::Begin => vec![ProtoEvent::Enter(opposite_pos)],
+            CaptureEvent::Lost => unreachable!("capture is released above"),
             CaptureEvent::Input(e) => match self.state {
                 // connection not acknowledged, repeat `Enter` event
-                State::WaitingForAck => ProtoEvent::Enter(opposite_pos),
-                State::Sending => ProtoEvent::Input(e),
+                State::WaitingForAck => vec![ProtoEvent::Enter(opposite_pos)],
+                State::Sending => self
+                    .transform(e)
+                    .into_iter()
+                    .map(ProtoEvent::Input)
+                    .collect(),
             },
         };
+        for event in events {
+            self.queue_event(capture, event, handle, begin).await?;
+        }
+        Ok(())
+    }
+
+    /// release the keys and buttons held on the client, so nothing stays
+    /// pressed while input is not forwarded
+    async fn release_pressed(
+        &mut self,
+        capture: &mut InputCapture,
+        handle: CaptureHandle,
+    ) -> Result<(), CaptureError> {
+        if self.active_client != Some(handle) || self.state != State::Sending {
+            return Ok(());
+        }
+        let keys = capture.pressed_keys().map(|key| {
+            Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key: key as u32,
+                state: 0,
+            })
+        });
+        let buttons = capture.pressed_buttons().map(|button| {
+            Event::Pointer(PointerEvent::Button {
+                time: 0,
+                button,
+                state: 0,
+            })
+        });
+        let releases: Vec<Event> = keys.chain(buttons).collect();
+        for release in releases {
+            for event in self.transform(release) {
+                self.queue_event(capture, ProtoEvent::Input(event), handle, false)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// send an event, holding back motion and scroll events if smoothing
+    /// or the packet interval floor requires it
+    async fn queue_event(
+        &mut self,
+        capture: &mut InputCapture,
+        event: ProtoEvent,
+        handle: CaptureHandle,
+        begin: bool,
+    ) -> Result<(), CaptureError> {
+        let event = match event {
+            ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy })) => {
+                let (dx, dy) = self.ramp_motion(dx, dy);
+                ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy }))
+            }
+            event => event,
+        };
+
+        // accumulate motion until the next tick if smoothing is enabled
+        // or until the packet interval floor has passed
+        if let ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy })) = event {
+            let rate_limited = self.rate_limited();
+            if self.motion_interval().is_some() || rate_limited {
+                if rate_limited && self.pending_motion.is_some() {
+                    self.packet_stats.merged += 1;
+                }
+                let (_, x, y) = self.pending_motion.unwrap_or_default();
+                self.pending_motion = Some((time, x + dx, y + dy));
+                return Ok(());
+            }
+        }
+
+        if self.rate_limited() {
+            match event {
+                // the client has not acknowledged yet, the next `Enter` will do
+                ProtoEvent::Enter(_) if !begin => {
+                    self.packet_stats.merged += 1;
+                    return Ok(());
+                }
+                ProtoEvent::Input(Event::Pointer(
+                    PointerEvent::Axis { .. } | PointerEvent::AxisDiscrete120 { .. },
+                )) => {
+                    self.packet_stats.deferred += 1;
+                    self.deferred.push(event);
+                    return Ok(());
+                }
+                // presses and releases are never delayed
+                _ => {}
+            }
+        }
+
+        // pending motion must arrive before any other event
+        self.flush_motion(capture).await?;
+        self.send(capture, event, handle).await
+    }
+
+    /// interval at which accumulated motion is sent, `None` if motion is sent immediately
+    fn motion_interval(&self) -> Option<Duration> {
+        let Some((min, max)) = self.adaptive_motion_interval else {
+            return self.motion_interval;
+        };
+        // accumulate for half a round trip
+        let rtt = self.active_client.and_then(|h| self.conn.rtt(h));
+        Some(rtt.map_or(min, |rtt| (rtt / 2).clamp(min, max)))
+    }
+
+    /// scale down motion right after a handoff, easing back to 1.0
+    fn ramp_motion(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let (Some((duration, min)), Some(handoff)) = (self.handoff_ramp, self.handoff) else {
+            return (dx, dy);
+        };
+        let progress = handoff.elapsed().as_secs_f64() / duration.as_secs_f64();
+        if progress >= 1. {
+            return (dx, dy);
+        }
+        let scale = min + (1. - min) * progress;
+        (dx * scale, dy * scale)
+    }
+
+    /// apply the transformers in order
+    fn transform(&mut self, event: Event) -> Vec<Event> {
+        let mut events = vec![event];
+        for transformer in self.transformers.iter_mut() {
+            events = events
+                .into_iter()
+                .flat_map(|e| transformer.transform(e))
+                .collect();
+        }
+        events
+    }
+
+    /// whether the packet interval floor has not passed since the last packet
+    fn rate_limited(&self) -> bool {
+        match (self.min_packet_interval, self.last_packet) {
+            (Some(min), Some(last)) => last.elapsed() < min,
+            _ => false,
+        }
+    }
+
+    /// whether events are waiting for the packet interval floor to pass
+    fn rate_limit_pending(&self) -> bool {
+        self.min_packet_interval.is_some()
+            && (!self.deferred.is_empty()
+                || (self.pending_motion.is_some() && self.motion_interval().is_none()))
+    }
+
+    /// time at which a crossing waiting for the modifier state is released
+    fn crossing_deadline(&self) -> time::Instant {
+        match self.crossing_pending {
+            Some((_, began)) => began + CROSSING_TIMEOUT,
+            None => time::Instant::now(),
+        }
+    }
+
+    /// time at which the next packet may be sent
+    fn next_packet(&self) -> time::Instant {
+        match (self.min_packet_interval, self.last_packet) {
+            (Some(min), Some(last)) => last + min,
+            _ => time::Instant::now(),
+        }
+    }
+
+    /// send accumulated motion, followed by deferred events
+    async fn flush_motion(&mut self, capture: &mut InputCapture) -> Result<(), CaptureError> {
+        let Some(handle) = self.active_client else {
+            return Ok(());
+        };
+        if let Some((time, dx, dy)) = self.pending_motion.take() {
+            let event = ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy }));
+            self.send(capture, event, handle).await?;
+        }
+        for event in std::mem::take(&mut self.deferred) {
+            self.send(capture, event, handle).await?;
+        }
+        Ok(())
+    }
 
+    /// round motion to whole pixels, see [`MotionRounding`].
+    /// Returns `None` if there is nothing to send yet.
+    fn round_motion(&mut self, event: ProtoEvent) -> Option<ProtoEvent> {
+        let ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy })) = event else {
+            return Some(event);
+        };
+        let (dx, dy) = self.motion_rounding.round(dx, dy)?;
+        Some(ProtoEvent::Input(Event::Pointer(PointerEvent::Motion {
+            time,
+            dx,
+            dy,
+        })))
+    }
+
+    async fn send(
+        &mut self,
+        capture: &mut InputCapture,
+        event: ProtoEvent,
+        handle: CaptureHandle,
+    ) -> Result<(), CaptureError> {
+        let event = match self.integer_motion {
+            true => match self.round_motion(event) {
+                Some(event) => event,
+                None => return Ok(()),
+            },
+            false => event,
+        };
+        self.last_packet = Some(time::Instant::now());
         if let Err(e) = self.conn.send(event, handle).await {
             const DUR: Duration = Duration::from_millis(500);
             debounce!(PREV_LOG, DUR, log::warn!("releasing capture: {e}"));
+            self.set_cursor_owner(None);
             capture.release().await?;
         }
         Ok(())
@@ -363,6 +905,28 @@ impl CaptureTask {
 
     async fn release_capture(&mut self, capture: &mut InputCapture) -> Result<(), CaptureError> {
         self.active_client.take();
+        self.crossing_pending.take();
+        self.pending_motion.take();
+        self.motion_rounding.reset();
+        self.deferred.clear();
+        let stats = std::mem::take(&mut self.packet_stats);
+        if stats.merged > 0 || stats.deferred > 0 {
+            log::info!(
+                "packet interval floor: {} events merged, {} events deferred",
+                stats.merged,
+                stats.deferred
+            );
+        }
+        let send_stats = self.conn.take_send_stats();
+        if send_stats.buffer_full > 0 {
+            log::info!(
+                "send buffer full {} times, {} motion events dropped",
+                send_stats.buffer_full,
+                send_stats.motion_dropped
+            );
+        }
+        self.set_paused(false);
+        self.set_cursor_owner(None);
         capture.release().await
     }
 }
@@ -371,6 +935,31 @@ thread_local! {
     static PREV_LOG: Cell<Option<Instant>> = const { Cell::new(None) };
 }
 
+/// create a capture, unless its span conflicts with another client at its position
+async fn create_capture(
+    capture: &mut InputCapture,
+    handle: CaptureHandle,
+    pos: Position,
+    span: EdgeSpan,
+) -> Result<(), CaptureError> {
+    match capture.create(handle, pos, span).await {
+        Err(e @ CaptureError::SpanConflict { .. }) => {
+            log::error!("not capturing for client {handle}: {e}");
+            Ok(())
+        }
+        r => r,
+    }
+}
+
+/// events affected by the packet interval floor during a capture
+#[derive(Debug, Default)]
+struct PacketStats {
+    /// events merged into another event
+    merged: u64,
+    /// events sent later than they were captured
+    deferred: u64,
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 enum State {
     #[default]
@@ -396,6 +985,32 @@ fn to_proto_pos(pos: input_capture::Position) -> lan_mouse_proto::Position {
     }
 }
 
+/// rounds motion to whole pixels, carrying the remainder over to the
+/// next event so that no motion is lost
+#[derive(Debug, Default)]
+struct MotionRounding {
+    remainder: (f64, f64),
+}
+
+impl MotionRounding {
+    /// whole pixel motion to send, `None` if there is nothing to send yet
+    fn round(&mut self, dx: f64, dy: f64) -> Option<(f64, f64)> {
+        let (rx, ry) = self.remainder;
+        let (x, y) = (rx + dx, ry + dy);
+        let (dx, dy) = (x.round(), y.round());
+        self.remainder = (x - dx, y - dy);
+        if dx == 0.0 && dy == 0.0 {
+            return None;
+        }
+        Some((dx, dy))
+    }
+
+    /// forget the remainder, e.g. when the capture is released
+    fn reset(&mut self) {
+        self.remainder = (0.0, 0.0);
+    }
+}
+
 struct DropGuard<T> {
     tx: Sender<T>,
     on_drop: Option<T>,
@@ -416,3 +1031,32 @@ impl<T> Drop for DropGuard<T> {
             .expect("channel closed");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounding_keeps_sub_pixel_motion() {
+        let mut rounding = MotionRounding::default();
+        let (mut x, mut y) = (0.0, 0.0);
+        for _ in 0..100 {
+            if let Some((dx, dy)) = rounding.round(1.4, -1.4) {
+                assert_eq!((dx.fract(), dy.fract()), (0.0, 0.0));
+                x += dx;
+                y += dy;
+            }
+        }
+        assert_eq!((x, y), (140.0, -140.0));
+    }
+
+    #[test]
+    fn rounding_reset() {
+        let mut rounding = MotionRounding::default();
+        assert_eq!(rounding.round(0.4, 0.0), None);
+        rounding.reset();
+        // the remainder of the previous capture is not carried over
+        assert_eq!(rounding.round(0.4, 0.0), None);
+        assert_eq!(rounding.round(0.2, 0.0), Some((1.0, 0.0)));
+    }
+}
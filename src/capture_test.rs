@@ -1,6 +1,8 @@
 use crate::config::Config;
 use futures::StreamExt;
-use input_capture::{self, CaptureError, CaptureEvent, InputCapture, InputCaptureError, Position};
+use input_capture::{
+    self, CaptureError, CaptureEvent, EdgeSpan, InputCapture, InputCaptureError, Position,
+};
 use input_event::{Event, KeyboardEvent};
 
 pub async fn run(config: Config) -> Result<(), InputCaptureError> {
@@ -10,19 +12,20 @@ pub async fn run(config: Config) -> Result<(), InputCaptureError> {
     loop {
         let mut input_capture = InputCapture::new(backend).await?;
         log::info!("creating clients");
-        input_capture.create(0, Position::Left).await?;
-        input_capture.create(4, Position::Left).await?;
-        input_capture.create(1, Position::Right).await?;
-        input_capture.create(2, Position::Top).await?;
-        input_capture.create(3, Position::Bottom).await?;
-        if let Err(e) = do_capture(&mut input_capture).await {
+        let span = EdgeSpan::default();
+        input_capture.create(0, Position::Left, span).await?;
+        input_capture.create(4, Position::Left, span).await?;
+        input_capture.create(1, Position::Right, span).await?;
+        input_capture.create(2, Position::Top, span).await?;
+        input_capture.create(3, Position::Bottom, span).await?;
+        if let Err(e) = do_capture(&mut input_capture, config.json_events).await {
             log::warn!("{e} - recreating capture");
         }
         let _ = input_capture.terminate().await;
     }
 }
 
-async fn do_capture(input_capture: &mut InputCapture) -> Result<(), CaptureError> {
+async fn do_capture(input_capture: &mut InputCapture, json: bool) -> Result<(), CaptureError> {
     loop {
         let (client, event) = input_capture
             .next()
@@ -35,10 +38,28 @@ async fn do_capture(input_capture: &mut InputCapture) -> Result<(), CaptureError
             3 => Position::Bottom,
             _ => panic!(),
         };
-        log::info!("position: {client} ({pos}), event: {event}");
+        if json {
+            print_json(client, pos, event);
+        } else {
+            log::info!("position: {client} ({pos}), event: {event}");
+        }
         if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key: 1, .. })) = event {
             input_capture.release().await?;
             break Ok(());
         }
     }
 }
+
+fn print_json(client: u64, pos: Position, event: CaptureEvent) {
+    let event = match event {
+        CaptureEvent::Begin => serde_json::json!("Begin"),
+        CaptureEvent::Input(e) => serde_json::json!(e),
+        CaptureEvent::Lost => serde_json::json!("Lost"),
+    };
+    let line = serde_json::json!({
+        "client": client,
+        "pos": pos.to_string(),
+        "event": event,
+    });
+    println!("{line}");
+}
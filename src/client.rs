@@ -3,8 +3,10 @@ use std::{
     collections::HashSet,
     net::{IpAddr, SocketAddr},
     rc::Rc,
+    time::Duration,
 };
 
+use input_event::{Event, PointerEvent};
 use slab::Slab;
 
 use lan_mouse_ipc::{ClientConfig, ClientHandle, ClientState, Position};
@@ -107,6 +109,80 @@ impl ClientManager {
             .map(|(c, _)| c.pos)
     }
 
+    /// get the section of the edge that triggers a handoff to this client
+    pub(crate) fn get_edge_span(&self, handle: ClientHandle) -> Option<(f64, f64)> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .and_then(|(c, _)| c.edge_span)
+    }
+
+    /// apply the axis configuration of a client to a pointer motion delta
+    pub(crate) fn transform_motion(&self, handle: ClientHandle, delta: (f64, f64)) -> (f64, f64) {
+        let clients = self.clients.borrow();
+        let Some((c, _)) = clients.get(handle as usize) else {
+            return delta;
+        };
+        let (dx, dy) = if c.swap_axes {
+            (delta.1, delta.0)
+        } else {
+            delta
+        };
+        let dx = if c.invert_x { -dx } else { dx };
+        let dy = if c.invert_y { -dy } else { dy };
+        (dx, dy)
+    }
+
+    /// whether the scroll direction of a client is inverted
+    pub(crate) fn inverts_scroll(&self, handle: ClientHandle) -> bool {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .is_some_and(|(c, _)| c.invert_scroll)
+    }
+
+    /// whether the given event is forwarded to a client
+    pub(crate) fn forwards(&self, handle: ClientHandle, event: &Event) -> bool {
+        let clients = self.clients.borrow();
+        let Some((c, _)) = clients.get(handle as usize) else {
+            return true;
+        };
+        match event {
+            Event::Pointer(PointerEvent::Motion { .. }) => c.forward.motion,
+            Event::Pointer(PointerEvent::Button { .. }) => c.forward.buttons,
+            Event::Pointer(PointerEvent::Axis { .. } | PointerEvent::AxisDiscrete120 { .. }) => {
+                c.forward.scroll
+            }
+            Event::Keyboard(_) => c.forward.keyboard,
+        }
+    }
+
+    /// whether handoffs to a client are suppressed while a local fullscreen window is focused
+    pub(crate) fn suppresses_in_fullscreen(&self, handle: ClientHandle) -> bool {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .is_some_and(|(c, _)| c.suppress_in_fullscreen)
+    }
+
+    /// time events are held back before they are sent to a client
+    pub(crate) fn send_delay(&self, handle: ClientHandle) -> Duration {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map_or(Duration::ZERO, |(c, _)| {
+                Duration::from_millis(c.send_delay_ms)
+            })
+    }
+
+    /// local address the connection to a client is sent from
+    pub(crate) fn source_ip(&self, handle: ClientHandle) -> Option<IpAddr> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .and_then(|(c, _)| c.source_ip)
+    }
+
     /// remove a client from the list
     pub fn remove_client(&self, client: ClientHandle) -> Option<(ClientConfig, ClientState)> {
         // remove id from occupied ids
@@ -1,29 +1,55 @@
-use clap::{Parser, ValueEnum};
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env::{self, VarError};
 use std::fmt::Display;
 use std::fs;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::{collections::HashSet, io};
 use thiserror::Error;
 use toml;
 
-use lan_mouse_ipc::{Position, DEFAULT_PORT};
+use lan_mouse_ipc::{InputFilter, Position, DEFAULT_PORT};
 
 use input_event::scancode::{
     self,
     Linux::{KeyLeftAlt, KeyLeftCtrl, KeyLeftMeta, KeyLeftShift},
 };
 
+use crate::loss_test::LossPattern;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ConfigToml {
     pub capture_backend: Option<CaptureBackend>,
     pub emulation_backend: Option<EmulationBackend>,
     pub port: Option<u16>,
     pub frontend: Option<Frontend>,
+    pub daemon: Option<bool>,
     pub release_bind: Option<Vec<scancode::Linux>>,
+    pub backend_switch_bind: Option<Vec<scancode::Linux>>,
+    pub pause_bind: Option<Vec<scancode::Linux>>,
+    pub cursor_return: Option<CursorReturn>,
+    pub lock_lost: Option<LockLost>,
+    pub corner_handoff: Option<CornerHandoff>,
+    pub arming_delay_ms: Option<u64>,
+    pub crossing_modifiers: Option<Vec<Modifier>>,
+    pub key_remap_file: Option<PathBuf>,
+    pub macro_file: Option<PathBuf>,
+    pub motion_interval_ms: Option<u64>,
+    pub adaptive_motion_interval_ms: Option<(u64, u64)>,
+    pub integer_motion: Option<bool>,
+    pub min_packet_interval_ms: Option<u64>,
+    pub handoff_ramp_ms: Option<u64>,
+    pub handoff_ramp_scale: Option<f64>,
+    pub motion_prediction_ms: Option<u64>,
+    pub send_buffer_size: Option<usize>,
+    pub recv_buffer_size: Option<usize>,
+    pub interface: Option<String>,
+    pub ttl: Option<u32>,
+    pub dscp: Option<u8>,
+    pub reachability_timeout_ms: Option<u64>,
     pub cert_path: Option<PathBuf>,
     pub left: Option<TomlClient>,
     pub right: Option<TomlClient>,
@@ -32,7 +58,7 @@ pub struct ConfigToml {
     pub authorized_fingerprints: Option<HashMap<String, String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct TomlClient {
     pub capture_backend: Option<CaptureBackend>,
     pub hostname: Option<String>,
@@ -41,6 +67,15 @@ pub struct TomlClient {
     pub port: Option<u16>,
     pub activate_on_startup: Option<bool>,
     pub enter_hook: Option<String>,
+    pub edge_span: Option<(f64, f64)>,
+    pub invert_x: Option<bool>,
+    pub invert_y: Option<bool>,
+    pub swap_axes: Option<bool>,
+    pub invert_scroll: Option<bool>,
+    pub forward: Option<Vec<InputKind>>,
+    pub suppress_in_fullscreen: Option<bool>,
+    pub send_delay_ms: Option<u64>,
+    pub source_ip: Option<IpAddr>,
 }
 
 impl ConfigToml {
@@ -48,6 +83,128 @@ impl ConfigToml {
         let config = fs::read_to_string(path)?;
         Ok(toml::from_str::<_>(&config)?)
     }
+
+    /// default configuration with a placeholder client
+    fn starter() -> Self {
+        let client = TomlClient {
+            capture_backend: None,
+            hostname: Some("my-peer".into()),
+            host_name: None,
+            ips: Some(vec![IpAddr::from([192, 168, 0, 2])]),
+            port: Some(DEFAULT_PORT),
+            activate_on_startup: Some(false),
+            enter_hook: None,
+            edge_span: Some((0., 1.)),
+            invert_x: None,
+            invert_y: None,
+            swap_axes: None,
+            invert_scroll: None,
+            forward: None,
+            suppress_in_fullscreen: None,
+            send_delay_ms: None,
+            source_ip: None,
+        };
+        Self {
+            capture_backend: None,
+            emulation_backend: None,
+            port: Some(DEFAULT_PORT),
+            frontend: None,
+            daemon: None,
+            release_bind: Some(DEFAULT_RELEASE_KEYS.to_vec()),
+            backend_switch_bind: None,
+            pause_bind: None,
+            cursor_return: Some(CursorReturn::default()),
+            lock_lost: None,
+            corner_handoff: None,
+            arming_delay_ms: None,
+            crossing_modifiers: None,
+            key_remap_file: None,
+            macro_file: None,
+            motion_interval_ms: None,
+            adaptive_motion_interval_ms: None,
+            integer_motion: None,
+            min_packet_interval_ms: None,
+            handoff_ramp_ms: None,
+            handoff_ramp_scale: None,
+            motion_prediction_ms: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            interface: None,
+            ttl: None,
+            dscp: None,
+            reachability_timeout_ms: None,
+            cert_path: None,
+            left: None,
+            right: Some(client),
+            top: None,
+            bottom: None,
+            authorized_fingerprints: Some(HashMap::from([(
+                "00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00".into(),
+                "my-peer".into(),
+            )])),
+        }
+    }
+
+    /// write a commented starter configuration to `path`,
+    /// an existing file is only replaced if `force` is set
+    pub fn write_starter(path: &Path, force: bool) -> Result<(), ConfigError> {
+        if !force && path.exists() {
+            return Err(ConfigError::Exists(path.to_owned()));
+        }
+        let config = toml::to_string(&Self::starter())?;
+        // make sure the generated config is accepted by the parser
+        toml::from_str::<Self>(&config)?;
+        let mut commented = String::from(STARTER_HEADER);
+        let mut section = "";
+        for line in config.lines() {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name;
+            }
+            let key = line.split_once(" = ").map(|(k, _)| k).unwrap_or(line);
+            if let Some(comment) = starter_comment(section, key) {
+                commented.push_str(comment);
+            }
+            commented.push_str(line);
+            commented.push('\n');
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, commented)?;
+        Ok(())
+    }
+}
+
+const STARTER_HEADER: &str = "\
+# lan-mouse configuration
+# see https://github.com/feschber/lan-mouse for all available options
+
+";
+
+/// comment preceding the given key of the starter configuration
+fn starter_comment(section: &str, key: &str) -> Option<&'static str> {
+    let comment = match (section, key) {
+        ("", "port") => "# port to listen on\n",
+        ("", "release_bind") => "# key combination that releases the captured cursor\n",
+        ("", "cursor_return") => {
+            "# where the cursor appears when it returns:\n# \"exit\", \"opposite-edge\" or \"center\"\n"
+        }
+        (_, "[authorized_fingerprints]") => {
+            "# tls certificate fingerprints of devices that may connect to this one\n"
+        }
+        (_, "[right]") => {
+            "# a client on the right side of this device,\n# [left], [top] and [bottom] work the same way\n"
+        }
+        ("right", "hostname") => "# hostname of the client\n",
+        ("right", "ips") => "# optional list of (known) ip addresses\n",
+        ("right", "port") => "# port the client listens on\n",
+        ("right", "activate_on_startup") => "# connect to the client on startup\n",
+        ("right", "edge_span") => {
+            "# part of the edge that hands off the cursor (start and end as fractions)\n"
+        }
+        _ => return None,
+    };
+    Some(comment)
 }
 
 #[derive(Parser, Debug)]
@@ -73,6 +230,10 @@ struct CliArgs {
     #[arg(long)]
     test_capture: bool,
 
+    /// print captured events as json lines to stdout (with --test-capture)
+    #[arg(long, requires = "test_capture")]
+    json: bool,
+
     /// test input emulation
     #[arg(long)]
     test_emulation: bool,
@@ -88,6 +249,48 @@ struct CliArgs {
     /// path to non-default certificate location
     #[arg(long)]
     cert_path: Option<PathBuf>,
+
+    /// write every sent and received packet to this file for debugging.
+    /// WARNING: the file contains every forwarded keystroke
+    #[arg(long)]
+    packet_log: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Copy, Debug, Subcommand)]
+pub enum Command {
+    /// write a commented starter config file
+    Init {
+        /// overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// check which capture and emulation backends work on this system.
+    /// Portal backends may ask for permission while being checked.
+    Doctor,
+    /// connect to every configured client and report whether it responds
+    Peers,
+    /// send scripted input through a simulated lossy link and report
+    /// stuck keys and reordered events seen by the receiver
+    LossTest {
+        /// percentage of packets to drop
+        #[arg(long, default_value_t = 5.0)]
+        drop_percent: f64,
+        /// distribution of dropped packets
+        #[arg(long, value_enum, default_value_t = LossPattern::Random)]
+        pattern: LossPattern,
+        /// number of consecutive packets dropped per burst (with --pattern burst)
+        #[arg(long, default_value_t = 8)]
+        burst_length: u32,
+        /// percentage of packets to delay by up to 40ms, so they arrive out of order
+        #[arg(long, default_value_t = 0.0)]
+        reorder_percent: f64,
+        /// number of events to send (one per millisecond)
+        #[arg(long, default_value_t = 5000)]
+        events: u32,
+    },
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
@@ -147,6 +350,105 @@ impl From<CaptureBackend> for input_capture::Backend {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CursorReturn {
+    #[default]
+    #[serde(rename = "exit")]
+    Exit,
+    #[serde(rename = "opposite-edge")]
+    OppositeEdge,
+    #[serde(rename = "center")]
+    Center,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LockLost {
+    #[default]
+    #[serde(rename = "release")]
+    Release,
+    #[serde(rename = "relock")]
+    Relock,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CornerHandoff {
+    #[serde(rename = "any")]
+    Any,
+    #[default]
+    #[serde(rename = "horizontal")]
+    Horizontal,
+    #[serde(rename = "vertical")]
+    Vertical,
+    #[serde(rename = "ignore")]
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum InputKind {
+    #[serde(rename = "motion")]
+    Motion,
+    #[serde(rename = "buttons")]
+    Buttons,
+    #[serde(rename = "scroll")]
+    Scroll,
+    #[serde(rename = "keyboard")]
+    Keyboard,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Modifier {
+    #[serde(rename = "shift")]
+    Shift,
+    #[serde(rename = "ctrl")]
+    Ctrl,
+    #[serde(rename = "alt")]
+    Alt,
+    #[serde(rename = "super")]
+    Super,
+}
+
+impl Modifier {
+    /// xkb modifier mask (as reported by `wl_keyboard.modifiers`)
+    fn mask(self) -> u32 {
+        match self {
+            Modifier::Shift => 1 << 0,
+            Modifier::Ctrl => 1 << 2,
+            Modifier::Alt => 1 << 3,
+            Modifier::Super => 1 << 6,
+        }
+    }
+}
+
+impl From<LockLost> for input_capture::LockLost {
+    fn from(lock_lost: LockLost) -> Self {
+        match lock_lost {
+            LockLost::Release => Self::Release,
+            LockLost::Relock => Self::Relock,
+        }
+    }
+}
+
+impl From<CornerHandoff> for input_capture::CornerHandoff {
+    fn from(corner_handoff: CornerHandoff) -> Self {
+        match corner_handoff {
+            CornerHandoff::Any => Self::Any,
+            CornerHandoff::Horizontal => Self::Horizontal,
+            CornerHandoff::Vertical => Self::Vertical,
+            CornerHandoff::Ignore => Self::Ignore,
+        }
+    }
+}
+
+impl From<CursorReturn> for input_capture::CursorReturn {
+    fn from(cursor_return: CursorReturn) -> Self {
+        match cursor_return {
+            CursorReturn::Exit => Self::Exit,
+            CursorReturn::OppositeEdge => Self::OppositeEdge,
+            CursorReturn::Center => Self::Center,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
 pub enum EmulationBackend {
     #[cfg(all(unix, feature = "wlroots_emulation", not(target_os = "macos")))]
@@ -233,6 +535,10 @@ impl Default for Frontend {
 pub struct Config {
     /// the path to the configuration file used
     pub path: PathBuf,
+    /// the configuration file
+    pub config_file: PathBuf,
+    /// subcommand to run instead of the app
+    pub command: Option<Command>,
     /// public key fingerprints authorized for connection
     pub authorized_fingerprints: HashMap<String, String>,
     /// optional input-capture backend override
@@ -249,12 +555,53 @@ pub struct Config {
     pub daemon: bool,
     /// configured release bind
     pub release_bind: Vec<scancode::Linux>,
+    /// bind to switch to the next capture backend, disabled if empty
+    pub backend_switch_bind: Vec<scancode::Linux>,
+    /// bind to pause forwarding of captured input, disabled if empty
+    pub pause_bind: Vec<scancode::Linux>,
+    /// where the cursor is placed when it returns from a client
+    pub cursor_return: CursorReturn,
+    /// what happens when the compositor breaks the pointer lock
+    pub lock_lost: LockLost,
+    /// client the cursor is handed off to in a corner where two clients meet
+    pub corner_handoff: CornerHandoff,
+    /// time the pointer has to rest at the edge before it is captured
+    pub arming_delay: Duration,
+    /// modifier mask that has to be held to hand off the cursor, 0 if disabled
+    pub crossing_modifiers: u32,
+    /// keys that are replaced before being sent to a client
+    pub key_remap: HashMap<scancode::Linux, scancode::Linux>,
+    /// keys that are expanded into a sequence of key chords
+    pub macros: HashMap<scancode::Linux, Vec<Vec<scancode::Linux>>>,
+    /// interval at which accumulated pointer motion is sent,
+    /// `None` to send motion as soon as it is captured
+    pub motion_interval: Option<Duration>,
+    /// bounds of the motion interval adapted to the round trip time
+    /// of the client, overrides `motion_interval`
+    pub adaptive_motion_interval: Option<(Duration, Duration)>,
+    /// round pointer motion to whole pixels before sending
+    pub integer_motion: bool,
+    /// minimum time between two packets sent to a client, `None` if unlimited
+    pub min_packet_interval: Option<Duration>,
+    /// duration of the sensitivity ramp after a handoff and the scale it starts at
+    pub handoff_ramp: Option<(Duration, f64)>,
+    /// time received motion is extrapolated ahead, `None` to disable prediction
+    pub motion_prediction: Option<Duration>,
+    /// options applied to the sockets of outgoing connections
+    pub socket_options: SocketOptions,
+    /// time within which a client must have answered a ping
+    /// to hand off the cursor, `None` to always hand it off
+    pub reachability_timeout: Option<Duration>,
     /// test capture instead of running the app
     pub test_capture: bool,
+    /// print captured events as json when testing capture
+    pub json_events: bool,
     /// test emulation instead of running the app
     pub test_emulation: bool,
     /// path to the tls certificate to use
     pub cert_path: PathBuf,
+    /// file to log all packets to
+    pub packet_log: Option<PathBuf>,
 }
 
 pub struct ConfigClient {
@@ -264,6 +611,31 @@ pub struct ConfigClient {
     pub pos: Position,
     pub active: bool,
     pub enter_hook: Option<String>,
+    pub edge_span: Option<(f64, f64)>,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub swap_axes: bool,
+    pub invert_scroll: bool,
+    pub forward: InputFilter,
+    pub suppress_in_fullscreen: bool,
+    pub send_delay_ms: u64,
+    pub source_ip: Option<IpAddr>,
+}
+
+/// options applied to the sockets of outgoing connections,
+/// `None` leaves the system default
+#[derive(Clone, Debug, Default)]
+pub struct SocketOptions {
+    /// requested `SO_SNDBUF` size
+    pub send_buffer: Option<usize>,
+    /// requested `SO_RCVBUF` size
+    pub recv_buffer: Option<usize>,
+    /// network interface to bind to (`SO_BINDTODEVICE`)
+    pub interface: Option<String>,
+    /// `IP_TTL` of sent packets
+    pub ttl: Option<u32>,
+    /// DSCP value of sent packets (upper 6 bits of `IP_TOS`)
+    pub dscp: Option<u8>,
 }
 
 #[derive(Debug, Error)]
@@ -271,14 +643,126 @@ pub enum ConfigError {
     #[error(transparent)]
     Toml(#[from] toml::de::Error),
     #[error(transparent)]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("{0:?} already exists, use --force to overwrite it")]
+    Exists(PathBuf),
+    #[error("{path:?}: {}", errors.join(", "))]
+    KeyRemap { path: PathBuf, errors: Vec<String> },
+    #[error("{path:?}: {}", errors.join(", "))]
+    Macro { path: PathBuf, errors: Vec<String> },
+    #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
     Var(#[from] VarError),
 }
 
+/// load a key translation table.
+/// Every line maps a key to its replacement (`KeyA = KeyB`),
+/// empty lines and lines starting with `#` are ignored.
+fn load_key_remap(path: &Path) -> Result<HashMap<scancode::Linux, scancode::Linux>, ConfigError> {
+    let mut remap = HashMap::new();
+    let mut errors = vec![];
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((from, to)) = line.split_once('=') else {
+            errors.push(format!("line {}: expected `<key> = <key>`", i + 1));
+            continue;
+        };
+        let (from, to) = (from.trim(), to.trim());
+        match (parse_key(from), parse_key(to)) {
+            (Some(from), Some(to)) => {
+                remap.insert(from, to);
+            }
+            (f, t) => {
+                for (name, key) in [(from, f), (to, t)] {
+                    if key.is_none() {
+                        errors.push(format!("line {}: unknown key `{name}`", i + 1));
+                    }
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ConfigError::KeyRemap {
+            path: path.to_owned(),
+            errors,
+        });
+    }
+    Ok(remap)
+}
+
+/// load key macros.
+/// Every line maps a key to a sequence of chords separated by spaces
+/// (`KeyF13 = KeyLeftShift+KeyH KeyI`), the keys of a chord are pressed
+/// in order and released in reverse order.
+/// Empty lines and lines starting with `#` are ignored.
+fn load_macros(
+    path: &Path,
+) -> Result<HashMap<scancode::Linux, Vec<Vec<scancode::Linux>>>, ConfigError> {
+    let mut macros = HashMap::new();
+    let mut errors = vec![];
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, sequence)) = line.split_once('=') else {
+            errors.push(format!("line {}: expected `<key> = <chord> ...`", i + 1));
+            continue;
+        };
+        let key = key.trim();
+        let Some(trigger) = parse_key(key) else {
+            errors.push(format!("line {}: unknown key `{key}`", i + 1));
+            continue;
+        };
+        let mut chords = vec![];
+        for chord in sequence.split_whitespace() {
+            let mut keys = vec![];
+            for name in chord.split('+') {
+                match parse_key(name) {
+                    Some(key) => keys.push(key),
+                    None => errors.push(format!("line {}: unknown key `{name}`", i + 1)),
+                }
+            }
+            chords.push(keys);
+        }
+        if chords.is_empty() {
+            errors.push(format!("line {}: empty macro", i + 1));
+        }
+        if macros.insert(trigger, chords).is_some() {
+            errors.push(format!("line {}: duplicate macro for `{key}`", i + 1));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ConfigError::Macro {
+            path: path.to_owned(),
+            errors,
+        });
+    }
+    Ok(macros)
+}
+
+fn parse_key(name: &str) -> Option<scancode::Linux> {
+    scancode::Linux::deserialize(name.into_deserializer())
+        .map_err(|_: serde::de::value::Error| ())
+        .ok()
+}
+
 const DEFAULT_RELEASE_KEYS: [scancode::Linux; 4] =
     [KeyLeftCtrl, KeyLeftShift, KeyLeftMeta, KeyLeftAlt];
 
+/// DSCP is a 6 bit field
+const MAX_DSCP: u8 = 63;
+
+/// predicting further ahead overshoots noticeably
+const MAX_MOTION_PREDICTION_MS: u64 = 10;
+
+/// motion scale at the start of the handoff ramp, unless configured
+const DEFAULT_HANDOFF_RAMP_SCALE: f64 = 0.5;
+
 impl Config {
     pub fn new() -> Result<Self, ConfigError> {
         let args = CliArgs::parse();
@@ -306,6 +790,13 @@ impl Config {
         let config_file = args.config.map(PathBuf::from).unwrap_or(config_file);
 
         let mut config_toml = match ConfigToml::new(&config_file) {
+            // no config file is expected when creating one
+            Err(ConfigError::Io(e))
+                if e.kind() == io::ErrorKind::NotFound
+                    && matches!(args.command, Some(Command::Init { .. })) =>
+            {
+                None
+            }
             Err(e) => {
                 log::warn!("{config_file:?}: {e}");
                 log::warn!("Continuing without config file ...");
@@ -329,6 +820,127 @@ impl Config {
             .and_then(|c| c.release_bind.clone())
             .unwrap_or(Vec::from_iter(DEFAULT_RELEASE_KEYS.iter().cloned()));
 
+        let backend_switch_bind = config_toml
+            .as_ref()
+            .and_then(|c| c.backend_switch_bind.clone())
+            .unwrap_or_default();
+
+        let pause_bind = config_toml
+            .as_ref()
+            .and_then(|c| c.pause_bind.clone())
+            .unwrap_or_default();
+
+        let cursor_return = config_toml
+            .as_ref()
+            .and_then(|c| c.cursor_return)
+            .unwrap_or_default();
+
+        let lock_lost = config_toml
+            .as_ref()
+            .and_then(|c| c.lock_lost)
+            .unwrap_or_default();
+
+        let corner_handoff = config_toml
+            .as_ref()
+            .and_then(|c| c.corner_handoff)
+            .unwrap_or_default();
+
+        let crossing_modifiers = config_toml
+            .as_ref()
+            .and_then(|c| c.crossing_modifiers.as_ref())
+            .map(|m| m.iter().fold(0, |mask, m| mask | m.mask()))
+            .unwrap_or_default();
+
+        // relative paths are relative to the directory of the config file,
+        // which is not the default config directory if --config is given
+        let config_dir = config_file.parent().unwrap_or(&config_path);
+
+        let key_remap = match config_toml.as_ref().and_then(|c| c.key_remap_file.as_ref()) {
+            Some(file) => load_key_remap(&config_dir.join(file))?,
+            None => Default::default(),
+        };
+
+        let macros = match config_toml.as_ref().and_then(|c| c.macro_file.as_ref()) {
+            Some(file) => load_macros(&config_dir.join(file))?,
+            None => Default::default(),
+        };
+
+        let arming_delay = config_toml
+            .as_ref()
+            .and_then(|c| c.arming_delay_ms)
+            .map(Duration::from_millis)
+            .unwrap_or_default();
+
+        let motion_interval = config_toml
+            .as_ref()
+            .and_then(|c| c.motion_interval_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis);
+
+        let adaptive_motion_interval = config_toml
+            .as_ref()
+            .and_then(|c| c.adaptive_motion_interval_ms)
+            .map(|(a, b)| {
+                (
+                    Duration::from_millis(a.min(b)),
+                    Duration::from_millis(a.max(b)),
+                )
+            });
+
+        let integer_motion = config_toml
+            .as_ref()
+            .and_then(|c| c.integer_motion)
+            .unwrap_or(false);
+
+        let min_packet_interval = config_toml
+            .as_ref()
+            .and_then(|c| c.min_packet_interval_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis);
+
+        let handoff_ramp = config_toml
+            .as_ref()
+            .and_then(|c| c.handoff_ramp_ms)
+            .filter(|&ms| ms > 0)
+            .map(|ms| {
+                let scale = config_toml
+                    .as_ref()
+                    .and_then(|c| c.handoff_ramp_scale)
+                    .filter(|s| !s.is_nan())
+                    .unwrap_or(DEFAULT_HANDOFF_RAMP_SCALE)
+                    .clamp(0., 1.);
+                (Duration::from_millis(ms), scale)
+            });
+
+        let motion_prediction = config_toml
+            .as_ref()
+            .and_then(|c| c.motion_prediction_ms)
+            .filter(|&ms| ms > 0)
+            .map(|ms| {
+                if ms > MAX_MOTION_PREDICTION_MS {
+                    log::warn!("motion_prediction_ms is limited to {MAX_MOTION_PREDICTION_MS}");
+                }
+                Duration::from_millis(ms.min(MAX_MOTION_PREDICTION_MS))
+            });
+
+        let reachability_timeout = config_toml
+            .as_ref()
+            .and_then(|c| c.reachability_timeout_ms)
+            .map(Duration::from_millis);
+
+        let socket_options = SocketOptions {
+            send_buffer: config_toml.as_ref().and_then(|c| c.send_buffer_size),
+            recv_buffer: config_toml.as_ref().and_then(|c| c.recv_buffer_size),
+            interface: config_toml.as_ref().and_then(|c| c.interface.clone()),
+            ttl: config_toml.as_ref().and_then(|c| c.ttl),
+            dscp: config_toml.as_ref().and_then(|c| c.dscp).filter(|&dscp| {
+                if dscp > MAX_DSCP {
+                    log::warn!("ignoring dscp {dscp}: must be at most {MAX_DSCP}");
+                }
+                dscp <= MAX_DSCP
+            }),
+        };
+
         let capture_backend = args
             .capture_backend
             .or(config_toml.as_ref().and_then(|c| c.capture_backend));
@@ -347,6 +959,8 @@ impl Config {
             .and_then(|c| std::mem::take(&mut c.authorized_fingerprints))
             .unwrap_or_default();
 
+        let daemon = args.daemon || config_toml.as_ref().and_then(|c| c.daemon).unwrap_or(false);
+
         let mut clients: Vec<(TomlClient, Position)> = vec![];
 
         if let Some(config_toml) = config_toml {
@@ -364,12 +978,14 @@ impl Config {
             }
         }
 
-        let daemon = args.daemon;
         let test_capture = args.test_capture;
+        let json_events = args.json;
         let test_emulation = args.test_emulation;
 
         Ok(Config {
             path: config_path,
+            config_file,
+            command: args.command,
             authorized_fingerprints,
             capture_backend,
             emulation_backend,
@@ -378,9 +994,28 @@ impl Config {
             clients,
             port,
             release_bind,
+            backend_switch_bind,
+            pause_bind,
+            cursor_return,
+            lock_lost,
+            corner_handoff,
+            arming_delay,
+            crossing_modifiers,
+            key_remap,
+            macros,
+            motion_interval,
+            adaptive_motion_interval,
+            integer_motion,
+            min_packet_interval,
+            handoff_ramp,
+            motion_prediction,
+            socket_options,
+            reachability_timeout,
             test_capture,
+            json_events,
             test_emulation,
             cert_path,
+            packet_log: args.packet_log,
         })
     }
 
@@ -400,6 +1035,22 @@ impl Config {
                 };
                 let active = c.activate_on_startup.unwrap_or(false);
                 let enter_hook = c.enter_hook.clone();
+                let edge_span = c.edge_span;
+                let invert_x = c.invert_x.unwrap_or(false);
+                let invert_y = c.invert_y.unwrap_or(false);
+                let swap_axes = c.swap_axes.unwrap_or(false);
+                let invert_scroll = c.invert_scroll.unwrap_or(false);
+                let forward = match c.forward.as_ref() {
+                    Some(kinds) => InputFilter {
+                        motion: kinds.contains(&InputKind::Motion),
+                        buttons: kinds.contains(&InputKind::Buttons),
+                        scroll: kinds.contains(&InputKind::Scroll),
+                        keyboard: kinds.contains(&InputKind::Keyboard),
+                    },
+                    None => InputFilter::default(),
+                };
+                let suppress_in_fullscreen = c.suppress_in_fullscreen.unwrap_or(false);
+                let send_delay_ms = c.send_delay_ms.unwrap_or(0);
                 ConfigClient {
                     ips,
                     hostname,
@@ -407,6 +1058,15 @@ impl Config {
                     pos: *pos,
                     active,
                     enter_hook,
+                    edge_span,
+                    invert_x,
+                    invert_y,
+                    swap_axes,
+                    invert_scroll,
+                    forward,
+                    suppress_in_fullscreen,
+                    send_delay_ms,
+                    source_ip: c.source_ip,
                 }
             })
             .collect()
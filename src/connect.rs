@@ -1,12 +1,17 @@
-use crate::client::ClientManager;
-use lan_mouse_ipc::{ClientHandle, DEFAULT_PORT};
-use lan_mouse_proto::{ProtoEvent, MAX_EVENT_SIZE};
+use crate::{
+    client::ClientManager,
+    config::SocketOptions,
+    sequence::{self, Packet, SequencedConn},
+};
+use input_event::{Event, PointerEvent};
+use lan_mouse_ipc::{ClientHandle, DisconnectReason, DEFAULT_PORT};
+use lan_mouse_proto::{ProtoEvent, ProtocolError};
 use local_channel::mpsc::{channel, Receiver, Sender};
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     io,
-    net::SocketAddr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     rc::Rc,
     sync::Arc,
     time::Duration,
@@ -16,6 +21,7 @@ use tokio::{
     net::UdpSocket,
     sync::Mutex,
     task::{spawn_local, JoinSet},
+    time::Instant,
 };
 use webrtc_dtls::{
     config::{Config, ExtendedMasterSecretType},
@@ -40,19 +46,161 @@ pub(crate) enum LanMouseConnectionError {
     Timeout,
 }
 
+/// state changes of outgoing connections
+pub(crate) enum ConnectionEvent {
+    /// connected to the client at the given address
+    Connected(ClientHandle, SocketAddr),
+    /// connecting to the client failed
+    Failed(ClientHandle, String),
+    /// the connection to the client ended
+    Disconnected(ClientHandle, DisconnectReason),
+    /// the round trip time to the client changed noticeably
+    Rtt(ClientHandle, Duration),
+}
+
+/// ping state of each address
+#[derive(Default)]
+struct PingState {
+    /// time the last ping was sent
+    sent: Option<Instant>,
+    /// time the last pong was received
+    received: Option<Instant>,
+    /// smoothed round trip time
+    rtt: Option<Duration>,
+    /// time and value of the last reported round trip time
+    reported: Option<(Instant, Duration)>,
+}
+
+impl PingState {
+    /// record a pong, returns the round trip time if it should be reported
+    fn pong(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.received = Some(now);
+        let sample = now - self.sent?;
+        // smoothed like the TCP round trip time (RFC 6298)
+        let rtt = match self.rtt {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        };
+        self.rtt = Some(rtt);
+        let changed = match self.reported {
+            None => true,
+            Some((time, reported)) => {
+                now - time >= RTT_REPORT_INTERVAL
+                    && rtt.abs_diff(reported) * RTT_REPORT_CHANGE > reported
+            }
+        };
+        if changed {
+            self.reported = Some((now, rtt));
+        }
+        changed.then_some(rtt)
+    }
+}
+
+type Pings = Rc<RefCell<HashMap<SocketAddr, PingState>>>;
+
 const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// minimum time between two reports of the round trip time of a client
+const RTT_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// the round trip time is reported once it changed by more than 1 / 4
+const RTT_REPORT_CHANGE: u32 = 4;
+
+/// time to wait for the socket buffer to drain before sending queued packets again
+const SEND_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// apply the configured buffer sizes, the kernel may clamp them
+fn set_buffer_sizes(socket: &UdpSocket, options: &SocketOptions) -> io::Result<()> {
+    let socket = socket2::SockRef::from(socket);
+    if let Some(size) = options.send_buffer {
+        socket.set_send_buffer_size(size)?;
+        let actual = socket.send_buffer_size()?;
+        log::info!("send buffer size: requested {size}, got {actual}");
+    }
+    if let Some(size) = options.recv_buffer {
+        socket.set_recv_buffer_size(size)?;
+        let actual = socket.recv_buffer_size()?;
+        log::info!("recv buffer size: requested {size}, got {actual}");
+    }
+    Ok(())
+}
+
+/// set the configured ttl and dscp of sent packets
+fn set_qos(socket: &UdpSocket, options: &SocketOptions) -> io::Result<()> {
+    let socket = socket2::SockRef::from(socket);
+    if let Some(ttl) = options.ttl {
+        socket.set_ttl(ttl)?;
+        log::info!("ttl: {}", socket.ttl()?);
+    }
+    if let Some(dscp) = options.dscp {
+        // the lower two bits of the tos field are used for ECN
+        socket.set_tos(u32::from(dscp) << 2)?;
+        log::info!("dscp: {dscp}");
+    }
+    Ok(())
+}
+
+/// restrict the socket to the configured network interface
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_interface(socket: &UdpSocket, options: &SocketOptions) -> io::Result<()> {
+    if let Some(interface) = options.interface.as_ref() {
+        socket2::SockRef::from(socket).bind_device(Some(interface.as_bytes()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_interface(_socket: &UdpSocket, options: &SocketOptions) -> io::Result<()> {
+    if let Some(interface) = options.interface.as_ref() {
+        log::warn!("binding to interface {interface} is not supported on this platform");
+    }
+    Ok(())
+}
+
+/// warn if the configured interface does not exist
+fn check_interface(options: &SocketOptions) {
+    let Some(interface) = options.interface.as_ref() else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return;
+    };
+    let available = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if !available.contains(interface) {
+        log::error!(
+            "network interface {interface} does not exist, available interfaces: {}",
+            available.join(", ")
+        );
+    }
+}
+
 async fn connect(
     addr: SocketAddr,
+    source_ip: Option<IpAddr>,
     cert: Certificate,
+    options: Rc<SocketOptions>,
 ) -> Result<(Arc<dyn Conn + Sync + Send>, SocketAddr), (SocketAddr, LanMouseConnectionError)> {
     log::info!("connecting to {addr} ...");
-    let conn = Arc::new(
-        UdpSocket::bind("0.0.0.0:0")
-            .await
-            .map_err(|e| (addr, e.into()))?,
-    );
+    let source_ip = source_ip.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+    let socket = UdpSocket::bind((source_ip, 0))
+        .await
+        .map_err(|e| (addr, e.into()))?;
+    bind_interface(&socket, &options).map_err(|e| (addr, e.into()))?;
+    if let Err(e) = set_buffer_sizes(&socket, &options) {
+        log::warn!("failed to set socket buffer sizes: {e}");
+    }
+    if let Err(e) = set_qos(&socket, &options) {
+        log::warn!("failed to set ttl / dscp: {e}");
+    }
+    let conn = Arc::new(socket);
     conn.connect(addr).await.map_err(|e| (addr, e.into()))?;
+    if let Ok(local_addr) = conn.local_addr() {
+        log::info!("sending to {addr} from {local_addr}");
+    }
     let config = Config {
         certificates: vec![cert],
         server_name: "ignored".to_owned(),
@@ -70,13 +218,15 @@ async fn connect(
     }
 }
 
-async fn connect_any(
+pub(crate) async fn connect_any(
     addrs: &[SocketAddr],
+    source_ip: Option<IpAddr>,
     cert: Certificate,
+    options: Rc<SocketOptions>,
 ) -> Result<(Arc<dyn Conn + Send + Sync>, SocketAddr), LanMouseConnectionError> {
     let mut joinset = JoinSet::new();
     for &addr in addrs {
-        joinset.spawn_local(connect(addr, cert.clone()));
+        joinset.spawn_local(connect(addr, source_ip, cert.clone(), options.clone()));
     }
     loop {
         match joinset.join_next().await {
@@ -93,25 +243,72 @@ async fn connect_any(
 
 pub(crate) struct LanMouseConnection {
     cert: Certificate,
-    client_manager: ClientManager,
-    conns: Rc<Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>>,
+    socket_options: Rc<SocketOptions>,
+    ctx: ConnectionContext,
     connecting: Rc<Mutex<HashSet<ClientHandle>>>,
     recv_rx: Receiver<(ClientHandle, ProtoEvent)>,
     recv_tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
 }
 
+type Conns = Rc<Mutex<HashMap<SocketAddr, Arc<SequencedConn>>>>;
+
+/// state shared with the tasks of each connection
+#[derive(Clone)]
+struct ConnectionContext {
+    client_manager: ClientManager,
+    conns: Conns,
+    pings: Pings,
+    /// queues of events waiting for the send delay of their client
+    delayed: Delayed,
+    /// packets waiting for the socket buffer to drain
+    backlog: Backlog,
+    stats: Rc<RefCell<SendStats>>,
+    event_tx: Sender<ConnectionEvent>,
+}
+
+/// connection and packets of an address waiting for the socket buffer to drain
+type Backlog = Rc<RefCell<HashMap<SocketAddr, (Arc<SequencedConn>, VecDeque<Packet>)>>>;
+
+/// sends that hit a full socket buffer
+#[derive(Debug, Default)]
+pub(crate) struct SendStats {
+    /// sends that failed because the socket buffer was full
+    pub(crate) buffer_full: u64,
+    /// motion events dropped because the socket buffer was full
+    pub(crate) motion_dropped: u64,
+}
+
+/// connection and queue of a task sending delayed events
+type DelayQueue = (Arc<SequencedConn>, Sender<(Instant, ProtoEvent)>);
+
+/// delay queue of each address
+type Delayed = Rc<RefCell<HashMap<SocketAddr, DelayQueue>>>;
+
 impl LanMouseConnection {
-    pub(crate) fn new(cert: Certificate, client_manager: ClientManager) -> Self {
+    pub(crate) fn new(
+        cert: Certificate,
+        socket_options: SocketOptions,
+        client_manager: ClientManager,
+        event_tx: Sender<ConnectionEvent>,
+    ) -> Self {
+        check_interface(&socket_options);
         let (recv_tx, recv_rx) = channel();
-        Self {
-            cert,
+        let ctx = ConnectionContext {
             client_manager,
             conns: Default::default(),
+            pings: Default::default(),
+            delayed: Default::default(),
+            backlog: Default::default(),
+            stats: Default::default(),
+            event_tx,
+        };
+        Self {
+            cert,
+            socket_options: Rc::new(socket_options),
+            ctx,
             connecting: Default::default(),
             recv_rx,
             recv_tx,
-            ping_response: Default::default(),
         }
     }
 
@@ -124,57 +321,131 @@ impl LanMouseConnection {
         event: ProtoEvent,
         handle: ClientHandle,
     ) -> Result<(), LanMouseConnectionError> {
-        let (buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
-        let buf = &buf[..len];
-        if let Some(addr) = self.client_manager.active_addr(handle) {
+        if let ProtoEvent::Input(e) = &event {
+            if !self.ctx.client_manager.forwards(handle, e) {
+                return Ok(());
+            }
+        }
+        let event = match event {
+            ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy })) => {
+                let (dx, dy) = self.ctx.client_manager.transform_motion(handle, (dx, dy));
+                ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy }))
+            }
+            ProtoEvent::Input(Event::Pointer(PointerEvent::Axis { time, axis, value }))
+                if self.ctx.client_manager.inverts_scroll(handle) =>
+            {
+                let value = -value;
+                ProtoEvent::Input(Event::Pointer(PointerEvent::Axis { time, axis, value }))
+            }
+            ProtoEvent::Input(Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value }))
+                if self.ctx.client_manager.inverts_scroll(handle) =>
+            {
+                let value = -value;
+                ProtoEvent::Input(Event::Pointer(PointerEvent::AxisDiscrete120 {
+                    axis,
+                    value,
+                }))
+            }
+            event => event,
+        };
+        if let Some(addr) = self.ctx.client_manager.active_addr(handle) {
             let conn = {
-                let conns = self.conns.lock().await;
+                let conns = self.ctx.conns.lock().await;
                 conns.get(&addr).cloned()
             };
             if let Some(conn) = conn {
-                if !self.client_manager.alive(handle) {
+                if !self.ctx.client_manager.alive(handle) {
                     return Err(LanMouseConnectionError::TargetEmulationDisabled);
                 }
-                match conn.send(buf).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::warn!("client {handle} failed to send: {e}");
-                        disconnect(&self.client_manager, handle, addr, &self.conns).await;
-                    }
+                let delay = self.ctx.client_manager.send_delay(handle);
+                if !delay.is_zero() {
+                    self.send_delayed(handle, addr, conn, event, delay);
+                    return Ok(());
                 }
-                log::trace!("{event} >->->->->- {addr}");
+                let _ = self.ctx.send(handle, addr, &conn, event).await;
                 return Ok(());
             }
         }
 
-        // check if we are already trying to connect
+        self.connect(handle).await;
+        Err(LanMouseConnectionError::NotConnected)
+    }
+
+    /// queue an event to be sent once `delay` has passed.
+    /// Events to the same address are sent in the order they were queued.
+    fn send_delayed(
+        &self,
+        handle: ClientHandle,
+        addr: SocketAddr,
+        conn: Arc<SequencedConn>,
+        event: ProtoEvent,
+        delay: Duration,
+    ) {
+        let deadline = Instant::now() + delay;
+        let mut delayed = self.ctx.delayed.borrow_mut();
+        if let Some((c, tx)) = delayed.get(&addr) {
+            // the queue of a previous connection is discarded
+            if Arc::ptr_eq(c, &conn) && tx.send((deadline, event)).is_ok() {
+                return;
+            }
+        }
+        let (tx, rx) = channel();
+        tx.send((deadline, event)).expect("channel closed");
+        spawn_local(delay_loop(self.ctx.clone(), handle, addr, conn.clone(), rx));
+        delayed.insert(addr, (conn, tx));
+    }
+
+    /// sends that hit a full socket buffer since the last call
+    pub(crate) fn take_send_stats(&self) -> SendStats {
+        self.ctx.stats.take()
+    }
+
+    /// whether the client answered a ping within the given time
+    pub(crate) fn responded_within(&self, handle: ClientHandle, timeout: Duration) -> bool {
+        let Some(addr) = self.ctx.client_manager.active_addr(handle) else {
+            return false;
+        };
+        self.ctx
+            .pings
+            .borrow()
+            .get(&addr)
+            .and_then(|p| p.received)
+            .is_some_and(|t| t.elapsed() <= timeout)
+    }
+
+    /// smoothed round trip time to the client, if measured
+    pub(crate) fn rtt(&self, handle: ClientHandle) -> Option<Duration> {
+        let addr = self.ctx.client_manager.active_addr(handle)?;
+        self.ctx.pings.borrow().get(&addr).and_then(|p| p.rtt)
+    }
+
+    /// connect to the client in the background, if not already connecting
+    pub(crate) async fn connect(&self, handle: ClientHandle) {
         let mut connecting = self.connecting.lock().await;
         if !connecting.contains(&handle) {
             connecting.insert(handle);
             // connect in the background
             spawn_local(connect_to_handle(
-                self.client_manager.clone(),
+                self.ctx.clone(),
                 self.cert.clone(),
+                self.socket_options.clone(),
                 handle,
-                self.conns.clone(),
                 self.connecting.clone(),
                 self.recv_tx.clone(),
-                self.ping_response.clone(),
             ));
         }
-        Err(LanMouseConnectionError::NotConnected)
     }
 }
 
 async fn connect_to_handle(
-    client_manager: ClientManager,
+    ctx: ConnectionContext,
     cert: Certificate,
+    socket_options: Rc<SocketOptions>,
     handle: ClientHandle,
-    conns: Rc<Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>>,
     connecting: Rc<Mutex<HashSet<ClientHandle>>>,
     tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
 ) -> Result<(), LanMouseConnectionError> {
+    let client_manager = &ctx.client_manager;
     log::info!("client {handle} connecting ...");
     // sending did not work, figure out active conn.
     if let Some(addrs) = client_manager.get_ips(handle) {
@@ -184,46 +455,100 @@ async fn connect_to_handle(
             .map(|a| SocketAddr::new(a, port))
             .collect::<Vec<_>>();
         log::info!("client ({handle}) connecting ... (ips: {addrs:?})");
-        let res = connect_any(&addrs, cert).await;
+        let source_ip = client_manager.source_ip(handle);
+        let res = connect_any(&addrs, source_ip, cert, socket_options).await;
         let (conn, addr) = match res {
             Ok(c) => c,
             Err(e) => {
                 connecting.lock().await.remove(&handle);
+                let _ = ctx
+                    .event_tx
+                    .send(ConnectionEvent::Failed(handle, e.to_string()));
                 return Err(e);
             }
         };
         log::info!("client ({handle}) connected @ {addr}");
+        let _ = ctx.event_tx.send(ConnectionEvent::Connected(handle, addr));
+        let conn = Arc::new(SequencedConn::new(conn));
         client_manager.set_active_addr(handle, Some(addr));
-        conns.lock().await.insert(addr, conn.clone());
+        ctx.conns.lock().await.insert(addr, conn.clone());
         connecting.lock().await.remove(&handle);
 
         // poll connection for active
-        spawn_local(ping_pong(addr, conn.clone(), ping_response.clone()));
+        spawn_local(ping_pong(ctx.clone(), handle, addr, conn.clone()));
 
         // receiver
-        spawn_local(receive_loop(
-            client_manager,
-            handle,
-            addr,
-            conn,
-            conns,
-            tx,
-            ping_response.clone(),
-        ));
+        spawn_local(receive_loop(ctx.clone(), handle, addr, conn, tx));
         return Ok(());
     }
     connecting.lock().await.remove(&handle);
     Err(LanMouseConnectionError::NotConnected)
 }
 
+/// send queued events once their delay has passed
+async fn delay_loop(
+    ctx: ConnectionContext,
+    handle: ClientHandle,
+    addr: SocketAddr,
+    conn: Arc<SequencedConn>,
+    mut rx: Receiver<(Instant, ProtoEvent)>,
+) {
+    while let Some((deadline, event)) = rx.recv().await {
+        tokio::time::sleep_until(deadline).await;
+        if ctx.send(handle, addr, &conn, event).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// send the packets queued for `addr` in order, once the socket buffer drained
+async fn drain_backlog(
+    ctx: ConnectionContext,
+    handle: ClientHandle,
+    addr: SocketAddr,
+    conn: Arc<SequencedConn>,
+) {
+    loop {
+        tokio::time::sleep(SEND_RETRY_INTERVAL).await;
+        loop {
+            let packet = match ctx.backlog.borrow().get(&addr) {
+                Some((c, queue)) if Arc::ptr_eq(c, &conn) => queue.front().cloned(),
+                // the connection was closed
+                _ => return,
+            };
+            let Some(packet) = packet else {
+                ctx.backlog.borrow_mut().remove(&addr);
+                return;
+            };
+            match conn.send_packet(&packet).await {
+                Ok(_) => {
+                    if let Some((_, queue)) = ctx.backlog.borrow_mut().get_mut(&addr) {
+                        queue.pop_front();
+                    }
+                }
+                Err(e) if sequence::is_congested(&e) => break,
+                Err(e) => {
+                    log::warn!("client {handle} failed to send: {e}");
+                    let reason = DisconnectReason::Error(e.to_string());
+                    ctx.disconnect_conn(handle, addr, &conn, reason).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
 async fn ping_pong(
+    ctx: ConnectionContext,
+    handle: ClientHandle,
     addr: SocketAddr,
-    conn: Arc<dyn Conn + Send + Sync>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
+    conn: Arc<SequencedConn>,
 ) {
     loop {
-        let (buf, len) = ProtoEvent::Ping.into();
-        if let Err(e) = conn.send(&buf[..len]).await {
+        // the pong may arrive before `send` returns
+        let sent = Instant::now();
+        ctx.pings.borrow_mut().entry(addr).or_default().sent = Some(sent);
+        if let Err(e) = conn.send(ProtoEvent::Ping).await {
             log::warn!("{addr}: send error `{e}`, closing connection");
             let _ = conn.close().await;
             break;
@@ -232,8 +557,16 @@ async fn ping_pong(
 
         tokio::time::sleep(Duration::from_millis(500)).await;
 
-        if !ping_response.borrow_mut().remove(&addr) {
+        let responded = ctx
+            .pings
+            .borrow()
+            .get(&addr)
+            .and_then(|p| p.received)
+            .is_some_and(|t| t >= sent);
+        if !responded {
             log::warn!("{addr} did not respond, closing connection");
+            ctx.disconnect(handle, addr, DisconnectReason::Timeout)
+                .await;
             let _ = conn.close().await;
             return;
         }
@@ -241,41 +574,126 @@ async fn ping_pong(
 }
 
 async fn receive_loop(
-    client_manager: ClientManager,
+    ctx: ConnectionContext,
     handle: ClientHandle,
     addr: SocketAddr,
-    conn: Arc<dyn Conn + Send + Sync>,
-    conns: Rc<Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>>,
+    conn: Arc<SequencedConn>,
     tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
 ) {
-    let mut buf = [0u8; MAX_EVENT_SIZE];
-    while conn.recv(&mut buf).await.is_ok() {
-        if let Ok(event) = buf.try_into() {
-            log::trace!("{addr} <==<==<== {event}");
-            match event {
-                ProtoEvent::Pong(b) => {
-                    client_manager.set_active_addr(handle, Some(addr));
-                    client_manager.set_alive(handle, b);
-                    ping_response.borrow_mut().insert(addr);
+    // replies are handled in the order they arrive
+    let mut reason = DisconnectReason::Error("connection closed".into());
+    while let Ok(packet) = conn.recv().await {
+        match packet {
+            Ok((_seq, ProtoEvent::Pong(b))) => {
+                log::trace!("{addr} <==<==<== {}", ProtoEvent::Pong(b));
+                ctx.client_manager.set_active_addr(handle, Some(addr));
+                ctx.client_manager.set_alive(handle, b);
+                let rtt = ctx.pings.borrow_mut().entry(addr).or_default().pong();
+                if let Some(rtt) = rtt {
+                    let _ = ctx.event_tx.send(ConnectionEvent::Rtt(handle, rtt));
                 }
-                event => tx.send((handle, event)).expect("channel closed"),
             }
+            Ok((_seq, event)) => {
+                log::trace!("{addr} <==<==<== {event}");
+                tx.send((handle, event)).expect("channel closed");
+            }
+            Err(e @ ProtocolError::UnsupportedVersion(_)) => {
+                log::warn!("{addr}: {e}");
+                reason = DisconnectReason::Error(e.to_string());
+                break;
+            }
+            Err(_) => {}
         }
     }
     log::warn!("recv error");
-    disconnect(&client_manager, handle, addr, &conns).await;
+    ctx.disconnect(handle, addr, reason).await;
 }
 
-async fn disconnect(
-    client_manager: &ClientManager,
-    handle: ClientHandle,
-    addr: SocketAddr,
-    conns: &Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>,
-) {
-    log::warn!("client ({handle}) @ {addr} connection closed");
-    conns.lock().await.remove(&addr);
-    client_manager.set_active_addr(handle, None);
-    let active: Vec<SocketAddr> = conns.lock().await.keys().copied().collect();
-    log::info!("active connections: {active:?}");
+impl ConnectionContext {
+    /// send `event` over `conn`. Motion is dropped while the socket buffer
+    /// is full, other events are queued until it drained, so that no release
+    /// is lost. Fails if the connection was closed.
+    async fn send(
+        &self,
+        handle: ClientHandle,
+        addr: SocketAddr,
+        conn: &Arc<SequencedConn>,
+        event: ProtoEvent,
+    ) -> Result<(), webrtc_util::Error> {
+        let motion = matches!(
+            event,
+            ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { .. }))
+        );
+        // events stay behind those already waiting for the socket buffer
+        if let Some((c, queue)) = self.backlog.borrow_mut().get_mut(&addr) {
+            if Arc::ptr_eq(c, conn) {
+                if motion {
+                    self.stats.borrow_mut().motion_dropped += 1;
+                } else {
+                    queue.push_back(conn.encode(event));
+                }
+                return Ok(());
+            }
+        }
+        let packet = conn.encode(event);
+        match conn.send_packet(&packet).await {
+            Ok(_) => log::trace!("{event} >->->->->- {addr}"),
+            Err(e) if sequence::is_congested(&e) => {
+                let mut stats = self.stats.borrow_mut();
+                stats.buffer_full += 1;
+                if motion {
+                    // superseded by the next motion event anyway
+                    log::debug!("client {handle}: send buffer full, dropping {event}");
+                    stats.motion_dropped += 1;
+                    conn.discard(packet);
+                } else {
+                    log::debug!("client {handle}: send buffer full, queueing {event}");
+                    let queue = VecDeque::from([packet]);
+                    // the queue of a previous connection is discarded
+                    self.backlog
+                        .borrow_mut()
+                        .insert(addr, (conn.clone(), queue));
+                    spawn_local(drain_backlog(self.clone(), handle, addr, conn.clone()));
+                }
+            }
+            Err(e) => {
+                log::warn!("client {handle} failed to send: {e}");
+                let reason = DisconnectReason::Error(e.to_string());
+                self.disconnect_conn(handle, addr, conn, reason).await;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// close `conn`, unless the client is connected again already
+    async fn disconnect_conn(
+        &self,
+        handle: ClientHandle,
+        addr: SocketAddr,
+        conn: &Arc<SequencedConn>,
+        reason: DisconnectReason,
+    ) {
+        let current = self.conns.lock().await.get(&addr).cloned();
+        if current.is_some_and(|c| Arc::ptr_eq(&c, conn)) {
+            self.disconnect(handle, addr, reason).await;
+        }
+    }
+
+    async fn disconnect(&self, handle: ClientHandle, addr: SocketAddr, reason: DisconnectReason) {
+        // the connection may already be closed by another task
+        if self.conns.lock().await.remove(&addr).is_none() {
+            return;
+        }
+        // ends the tasks sending delayed and queued events to the closed connection
+        self.delayed.borrow_mut().remove(&addr);
+        self.backlog.borrow_mut().remove(&addr);
+        log::warn!("client ({handle}) @ {addr} connection closed: {reason}");
+        let _ = self
+            .event_tx
+            .send(ConnectionEvent::Disconnected(handle, reason));
+        self.client_manager.set_active_addr(handle, None);
+        let active: Vec<SocketAddr> = self.conns.lock().await.keys().copied().collect();
+        log::info!("active connections: {active:?}");
+    }
 }
@@ -0,0 +1,96 @@
+use std::fmt::Display;
+
+use input_capture::InputCapture;
+use input_emulation::InputEmulation;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DoctorError {
+    #[error("no input capture backend available")]
+    NoCapture,
+    #[error("no input emulation backend available")]
+    NoEmulation,
+}
+
+/// report which input capture and emulation backends can be created
+pub async fn run() -> Result<(), DoctorError> {
+    println!("input capture (sending input):");
+    let mut capture_ok = false;
+    for &backend in input_capture::BACKENDS {
+        let result = InputCapture::probe(backend).await;
+        capture_ok |= result.is_ok();
+        report(backend, result);
+    }
+
+    println!("input emulation (receiving input):");
+    let mut emulation_ok = false;
+    for &backend in input_emulation::BACKENDS {
+        let result = InputEmulation::probe(backend).await;
+        emulation_ok |= result.is_ok();
+        report(backend, result);
+    }
+
+    #[cfg(all(unix, feature = "layer_shell_capture", not(target_os = "macos")))]
+    let layer_shell_ok = {
+        println!("layer-shell globals (sending input):");
+        report_globals(InputCapture::layer_shell_globals())
+    };
+
+    #[cfg(all(unix, feature = "wlroots_emulation", not(target_os = "macos")))]
+    let wlroots_ok = {
+        println!("wlroots globals (receiving input):");
+        report_globals(InputEmulation::wlroots_globals())
+    };
+
+    println!();
+    println!("capture: {}", pass_fail(capture_ok));
+    println!("emulation: {}", pass_fail(emulation_ok));
+    #[cfg(all(unix, feature = "layer_shell_capture", not(target_os = "macos")))]
+    println!("layer-shell globals: {}", pass_fail(layer_shell_ok));
+    #[cfg(all(unix, feature = "wlroots_emulation", not(target_os = "macos")))]
+    println!("wlroots globals: {}", pass_fail(wlroots_ok));
+
+    if !capture_ok {
+        return Err(DoctorError::NoCapture);
+    }
+    if !emulation_ok {
+        return Err(DoctorError::NoEmulation);
+    }
+    Ok(())
+}
+
+fn report<B: Display, E: Display>(backend: B, result: Result<(), E>) {
+    match result {
+        Ok(()) => println!("  {backend}: ok"),
+        Err(e) => println!("  {backend}: unavailable ({e})"),
+    }
+}
+
+/// print whether each global is advertised, returns whether all of them are
+#[cfg(all(
+    unix,
+    any(feature = "layer_shell_capture", feature = "wlroots_emulation"),
+    not(target_os = "macos")
+))]
+fn report_globals<E: Display>(globals: Result<Vec<(&str, bool)>, E>) -> bool {
+    match globals {
+        Ok(globals) => {
+            for &(name, present) in &globals {
+                println!("  {name}: {}", if present { "ok" } else { "missing" });
+            }
+            globals.iter().all(|&(_, present)| present)
+        }
+        Err(e) => {
+            println!("  unavailable ({e})");
+            false
+        }
+    }
+}
+
+fn pass_fail(ok: bool) -> &'static str {
+    if ok {
+        "PASS"
+    } else {
+        "FAIL"
+    }
+}
@@ -1,7 +1,7 @@
 use crate::listen::{LanMouseListener, ListenerCreationError};
 use futures::StreamExt;
 use input_emulation::{EmulationHandle, InputEmulation, InputEmulationError};
-use input_event::Event;
+use input_event::{Event, PointerEvent};
 use lan_mouse_proto::{Position, ProtoEvent};
 use local_channel::mpsc::{channel, Receiver, Sender};
 use std::{
@@ -55,9 +55,10 @@ enum EmulationRequest {
 impl Emulation {
     pub(crate) fn new(
         backend: Option<input_emulation::Backend>,
+        motion_prediction: Option<Duration>,
         listener: LanMouseListener,
     ) -> Self {
-        let emulation_proxy = EmulationProxy::new(backend);
+        let emulation_proxy = EmulationProxy::new(backend, motion_prediction);
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let emulation_task = ListenTask {
@@ -118,7 +119,7 @@ struct ListenTask {
 impl ListenTask {
     async fn run(mut self) {
         let mut interval = tokio::time::interval(Duration::from_secs(5));
-        let mut last_response = HashMap::new();
+        let mut peers = Peers::default();
         loop {
             select! {
                 e = self.listener.next() =>  {
@@ -127,7 +128,7 @@ impl ListenTask {
                         None => break,
                     };
                     log::trace!("{event} <-<-<-<-<- {addr}");
-                    last_response.insert(addr, Instant::now());
+                    peers.received(addr, &event);
                     match event {
                         ProtoEvent::Enter(pos) => {
                             if let Some(fingerprint) = self.listener.get_certificate_fingerprint(addr).await {
@@ -147,6 +148,12 @@ impl ListenTask {
                     }
                 }
                 event = self.emulation_proxy.event() => {
+                    // tell connected devices right away instead of on their next ping,
+                    // so a device sending input releases its capture
+                    for (addr, notification) in peers.notifications(&event) {
+                        log::info!("emulation state changed: notifying {addr} ({notification})");
+                        self.listener.reply(addr, notification).await;
+                    }
                     self.event_tx.send(event).expect("channel closed");
                 }
                 request = self.request_rx.recv() => match request.expect("channel closed") {
@@ -162,16 +169,11 @@ impl ListenTask {
                     EmulationRequest::Terminate => break,
                 },
                 _ = interval.tick() => {
-                    last_response.retain(|&addr,instant| {
-                        if instant.elapsed() > Duration::from_secs(1) {
-                            log::warn!("releasing keys: {addr} not responding!");
-                            self.emulation_proxy.remove(addr);
-                            self.event_tx.send(EmulationEvent::Disconnected { addr }).expect("channel closed");
-                            false
-                        } else {
-                            true
-                        }
-                    });
+                    for addr in peers.expire(Duration::from_secs(1)) {
+                        log::warn!("releasing keys: {addr} not responding!");
+                        self.emulation_proxy.remove(addr);
+                        self.event_tx.send(EmulationEvent::Disconnected { addr }).expect("channel closed");
+                    }
                 }
             }
         }
@@ -180,6 +182,62 @@ impl ListenTask {
     }
 }
 
+/// devices connected to the listener
+#[derive(Default)]
+struct Peers {
+    /// time each device was last heard from
+    last_response: HashMap<SocketAddr, Instant>,
+    /// device currently sending input
+    sending: Option<SocketAddr>,
+}
+
+impl Peers {
+    fn received(&mut self, addr: SocketAddr, event: &ProtoEvent) {
+        self.last_response.insert(addr, Instant::now());
+        match event {
+            ProtoEvent::Enter(_) | ProtoEvent::Input(_) => self.sending = Some(addr),
+            ProtoEvent::Leave(_) if self.sending == Some(addr) => self.sending = None,
+            _ => {}
+        }
+    }
+
+    /// messages telling the devices about a change of the emulation state
+    fn notifications(&self, event: &EmulationEvent) -> Vec<(SocketAddr, ProtoEvent)> {
+        let active = match event {
+            EmulationEvent::EmulationDisabled => false,
+            EmulationEvent::EmulationEnabled => true,
+            _ => return vec![],
+        };
+        let mut notifications: Vec<_> = self
+            .last_response
+            .keys()
+            .map(|&addr| (addr, ProtoEvent::Pong(active)))
+            .collect();
+        // the device sending input releases its capture
+        if let Some(addr) = self.sending.filter(|_| !active) {
+            notifications.push((addr, ProtoEvent::Leave(0)));
+        }
+        notifications
+    }
+
+    /// forget devices that have not responded for `timeout`
+    fn expire(&mut self, timeout: Duration) -> Vec<SocketAddr> {
+        let expired: Vec<_> = self
+            .last_response
+            .iter()
+            .filter(|(_, instant)| instant.elapsed() > timeout)
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in &expired {
+            self.last_response.remove(addr);
+            if self.sending == Some(*addr) {
+                self.sending = None;
+            }
+        }
+        expired
+    }
+}
+
 /// proxy handling the actual input emulation,
 /// discarding events when it is disabled
 pub(crate) struct EmulationProxy {
@@ -198,7 +256,7 @@ enum ProxyRequest {
 }
 
 impl EmulationProxy {
-    fn new(backend: Option<input_emulation::Backend>) -> Self {
+    fn new(backend: Option<input_emulation::Backend>, motion_prediction: Option<Duration>) -> Self {
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let emulation_active = Rc::new(Cell::new(false));
@@ -210,6 +268,8 @@ impl EmulationProxy {
             event_tx,
             handles: Default::default(),
             next_id: 0,
+            motion_prediction,
+            predictors: Default::default(),
         };
         let task = spawn_local(emulation_task.run());
         Self {
@@ -269,6 +329,9 @@ struct EmulationTask {
     event_tx: Sender<EmulationEvent>,
     handles: HashMap<SocketAddr, EmulationHandle>,
     next_id: EmulationHandle,
+    /// time received motion is extrapolated ahead
+    motion_prediction: Option<Duration>,
+    predictors: HashMap<SocketAddr, MotionPredictor>,
 }
 
 impl EmulationTask {
@@ -350,9 +413,11 @@ impl EmulationTask {
                                 handle
                             }
                         };
+                        let event = self.predict(event, addr);
                         emulation.consume(event, handle).await?;
                     },
                     ProxyRequest::Remove(addr) => {
+                        self.predictors.remove(&addr);
                         if let Some(handle) = self.handles.remove(&addr) {
                             emulation.destroy(handle).await;
                         }
@@ -363,6 +428,86 @@ impl EmulationTask {
             }
         }
     }
+
+    fn predict(&mut self, event: Event, addr: SocketAddr) -> Event {
+        let (Some(lookahead), Event::Pointer(PointerEvent::Motion { time, dx, dy })) =
+            (self.motion_prediction, event)
+        else {
+            return event;
+        };
+        let predictor = self
+            .predictors
+            .entry(addr)
+            .or_insert_with(|| MotionPredictor::new(lookahead));
+        let (dx, dy) = predictor.predict((dx, dy));
+        Event::Pointer(PointerEvent::Motion { time, dx, dy })
+    }
+}
+
+/// motion events further apart than this start a new movement
+const MOVEMENT_GAP: Duration = Duration::from_millis(50);
+
+/// motion events closer than this arrived in the same burst
+/// and are not used to estimate the velocity
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_micros(500);
+
+/// moves the cursor ahead by the distance it travels in `lookahead`
+/// at the current velocity. The lead is built up and corrected through
+/// the received motion, which is never reversed or more than doubled,
+/// so the cursor does not visibly snap back.
+struct MotionPredictor {
+    lookahead: Duration,
+    /// estimated velocity in pixels per second
+    velocity: (f64, f64),
+    /// time the last motion event was received
+    last: Option<Instant>,
+    /// distance the emulated motion is ahead of the received motion
+    lead: (f64, f64),
+}
+
+impl MotionPredictor {
+    fn new(lookahead: Duration) -> Self {
+        Self {
+            lookahead,
+            velocity: (0.0, 0.0),
+            last: None,
+            lead: (0.0, 0.0),
+        }
+    }
+
+    /// motion to emulate for the received motion
+    fn predict(&mut self, (dx, dy): (f64, f64)) -> (f64, f64) {
+        let now = Instant::now();
+        match self.last.map(|last| now - last) {
+            Some(dt) if dt < MIN_SAMPLE_INTERVAL => {}
+            Some(dt) if dt < MOVEMENT_GAP => {
+                let dt = dt.as_secs_f64();
+                let (vx, vy) = self.velocity;
+                // smoothed to suppress jitter in the arrival times
+                self.velocity = ((vx + dx / dt) / 2.0, (vy + dy / dt) / 2.0);
+            }
+            _ => self.velocity = (0.0, 0.0),
+        }
+        self.last = Some(now);
+
+        let t = self.lookahead.as_secs_f64();
+        let (vx, vy) = self.velocity;
+        let (lx, ly) = self.lead;
+        let ex = correct(dx, vx * t - lx);
+        let ey = correct(dy, vy * t - ly);
+        self.lead = (lx + ex - dx, ly + ey - dy);
+        (ex, ey)
+    }
+}
+
+/// apply `correction` to the motion `d` without reversing or more than doubling it
+fn correct(d: f64, correction: f64) -> f64 {
+    let (min, max) = if d < 0.0 {
+        (2.0 * d, 0.0)
+    } else {
+        (0.0, 2.0 * d)
+    };
+    (d + correction).clamp(min, max)
 }
 
 fn to_ipc_pos(pos: Position) -> lan_mouse_ipc::Position {
@@ -405,3 +550,62 @@ impl<T> Drop for DropGuard<T> {
             .expect("channel closed");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_event::KeyboardEvent;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 168, 0, 2], port))
+    }
+
+    fn key() -> ProtoEvent {
+        ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key: 30,
+            state: 1,
+        }))
+    }
+
+    #[test]
+    fn emulation_disabled_releases_sending_device() {
+        let mut peers = Peers::default();
+        peers.received(addr(1), &ProtoEvent::Enter(Position::Left));
+        peers.received(addr(1), &key());
+        peers.received(addr(2), &ProtoEvent::Ping);
+
+        let mut notifications = peers.notifications(&EmulationEvent::EmulationDisabled);
+        notifications.sort_by_key(|(addr, _)| addr.port());
+        assert_eq!(
+            notifications,
+            vec![
+                (addr(1), ProtoEvent::Pong(false)),
+                (addr(1), ProtoEvent::Leave(0)),
+                (addr(2), ProtoEvent::Pong(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn emulation_disabled_after_leave() {
+        let mut peers = Peers::default();
+        peers.received(addr(1), &key());
+        peers.received(addr(1), &ProtoEvent::Leave(0));
+
+        let notifications = peers.notifications(&EmulationEvent::EmulationDisabled);
+        assert_eq!(notifications, vec![(addr(1), ProtoEvent::Pong(false))]);
+    }
+
+    #[test]
+    fn emulation_enabled() {
+        let mut peers = Peers::default();
+        peers.received(addr(1), &key());
+
+        let notifications = peers.notifications(&EmulationEvent::EmulationEnabled);
+        assert_eq!(notifications, vec![(addr(1), ProtoEvent::Pong(true))]);
+        assert!(peers
+            .notifications(&EmulationEvent::ReleaseNotify)
+            .is_empty());
+    }
+}
@@ -5,7 +5,13 @@ pub mod config;
 mod connect;
 mod crypto;
 mod dns;
+pub mod doctor;
 mod emulation;
 pub mod emulation_test;
 mod listen;
+pub mod loss_test;
+mod packet_log;
+pub mod peers;
+mod sequence;
 pub mod service;
+pub mod transform;
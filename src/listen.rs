@@ -1,18 +1,22 @@
 use futures::{Stream, StreamExt};
-use lan_mouse_proto::{ProtoEvent, MAX_EVENT_SIZE};
+use lan_mouse_proto::ProtoEvent;
 use local_channel::mpsc::{channel, Receiver, Sender};
 use rustls::pki_types::CertificateDer;
 use std::{
     collections::HashMap,
     net::SocketAddr,
     rc::Rc,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     time::Duration,
 };
 use thiserror::Error;
 use tokio::{
     sync::Mutex,
     task::{spawn_local, JoinHandle},
+    time::Instant,
 };
 use webrtc_dtls::{
     config::{ClientAuthType::RequireAnyClientCert, Config, ExtendedMasterSecretType},
@@ -20,9 +24,12 @@ use webrtc_dtls::{
     crypto::Certificate,
     listener::listen,
 };
-use webrtc_util::{conn::Listener, Conn, Error};
+use webrtc_util::{conn::Listener, Error};
 
-use crate::crypto;
+use crate::{
+    crypto,
+    sequence::{Reorder, SequencedConn},
+};
 
 #[derive(Error, Debug)]
 pub enum ListenerCreationError {
@@ -32,7 +39,7 @@ pub enum ListenerCreationError {
     WebrtcDtls(#[from] webrtc_dtls::Error),
 }
 
-type ArcConn = Arc<dyn Conn + Send + Sync>;
+type ArcConn = Arc<SequencedConn>;
 
 pub(crate) struct LanMouseListener {
     listen_rx: Receiver<(ProtoEvent, SocketAddr)>,
@@ -60,6 +67,7 @@ impl LanMouseListener {
         let (port_changed_tx, port_changed) = channel();
 
         let authorized = authorized_keys.clone();
+        let rejected = AtomicU64::new(0);
         let verify_peer_certificate: Option<VerifyPeerCertificateFn> = Some(Arc::new(
             move |certs: &[Vec<u8>], _chains: &[CertificateDer<'static>]| {
                 assert!(certs.len() == 1);
@@ -74,6 +82,11 @@ impl LanMouseListener {
                 {
                     Ok(())
                 } else {
+                    let rejected = rejected.fetch_add(1, Ordering::Relaxed) + 1;
+                    log::warn!(
+                        "rejecting connection from unauthorized device {} ({rejected} rejected so far)",
+                        fingerprints[0]
+                    );
                     Err(webrtc_dtls::Error::ErrVerifyDataMismatch)
                 }
             },
@@ -102,6 +115,7 @@ impl LanMouseListener {
                     c = listener.accept() => match c {
                         Ok((conn, addr)) => {
                             log::info!("dtls client connected, ip: {addr}");
+                            let conn = Arc::new(SequencedConn::new(conn));
                             let mut conns = conns_clone.lock().await;
                             conns.push((addr, conn.clone()));
                             spawn_local(read_loop(conns_clone.clone(), addr, conn, tx.clone()));
@@ -156,11 +170,10 @@ impl LanMouseListener {
 
     pub(crate) async fn reply(&self, addr: SocketAddr, event: ProtoEvent) {
         log::trace!("reply {event} >=>=>=>=>=> {addr}");
-        let (buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
         let conns = self.conns.lock().await;
         for (a, conn) in conns.iter() {
             if *a == addr {
-                let _ = conn.send(&buf[..len]).await;
+                let _ = conn.send(event).await;
             }
         }
     }
@@ -174,7 +187,7 @@ impl LanMouseListener {
             .find(|(a, _)| *a == addr)
             .map(|(_, c)| c.clone())
         {
-            let conn: &DTLSConn = conn.as_any().downcast_ref().expect("dtls conn");
+            let conn: &DTLSConn = conn.inner().as_any().downcast_ref().expect("dtls conn");
             let certs = conn.connection_state().await.peer_certificates;
             let cert = certs.first()?;
             let fingerprint = crypto::generate_fingerprint(cert);
@@ -202,15 +215,25 @@ async fn read_loop(
     conn: ArcConn,
     dtls_tx: Sender<(ProtoEvent, SocketAddr)>,
 ) -> Result<(), Error> {
-    let mut b = [0u8; MAX_EVENT_SIZE];
-
-    while conn.recv(&mut b).await.is_ok() {
-        match b.try_into() {
-            Ok(event) => dtls_tx.send((event, addr)).expect("channel closed"),
-            Err(e) => {
-                log::warn!("error receiving event: {e}");
-                break;
+    let mut reorder = Reorder::default();
+
+    loop {
+        let deadline = reorder.deadline();
+        let events = tokio::select! {
+            packet = conn.recv() => match packet {
+                Ok(Ok((seq, event))) => reorder.push(seq, event),
+                Ok(Err(e)) => {
+                    log::warn!("error receiving event: {e}");
+                    break;
+                }
+                Err(_) => break,
+            },
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                reorder.expire()
             }
+        };
+        for event in events {
+            dtls_tx.send((event, addr)).expect("channel closed");
         }
     }
     log::info!("dtls client disconnected {:?}", addr);
@@ -0,0 +1,330 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use input_event::{Event, KeyboardEvent, PointerEvent, BTN_LEFT};
+use lan_mouse_proto::ProtoEvent;
+use thiserror::Error;
+use tokio::time::Instant;
+
+use crate::sequence::Reorder;
+
+/// time between two sent events (a 1000Hz mouse)
+const EVENT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// maximum delay of a reordered packet, beyond the reorder timeout of the receiver
+const MAX_REORDER_DELAY_MS: u64 = 40;
+
+/// events sent per iteration of the script
+const SCRIPT_LEN: u32 = 14;
+
+#[derive(Debug, Error)]
+pub enum LossTestError {
+    #[error("percentage must be between 0 and 100, got {0}")]
+    Percent(f64),
+    #[error("burst length must be at least 1")]
+    BurstLength,
+}
+
+/// how dropped packets are distributed
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum LossPattern {
+    /// every packet is dropped independently
+    #[default]
+    Random,
+    /// packets are dropped in bursts of consecutive packets
+    Burst,
+}
+
+/// parameters of the simulated link
+#[derive(Clone, Copy, Debug)]
+pub struct LossTestOptions {
+    pub drop_percent: f64,
+    pub pattern: LossPattern,
+    pub burst_length: u32,
+    pub reorder_percent: f64,
+    pub events: u32,
+}
+
+/// send a scripted stream of key presses, button clicks and motion through
+/// a simulated lossy link into the receive path of lan-mouse and report the
+/// incidents seen by the receiver. Nothing is sent over the network.
+pub async fn run(options: LossTestOptions) -> Result<(), LossTestError> {
+    for percent in [options.drop_percent, options.reorder_percent] {
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(LossTestError::Percent(percent));
+        }
+    }
+    if options.burst_length == 0 {
+        return Err(LossTestError::BurstLength);
+    }
+
+    let mut link = LossyLink::new(options);
+    let mut reorder = Reorder::default();
+    let mut receiver = Receiver::default();
+    let start = Instant::now();
+
+    for seq in 0..options.events {
+        link.send(seq, scripted_event(seq));
+        for (seq, event) in link.due() {
+            receiver.receive_all(reorder.push(seq, event));
+        }
+        if reorder.deadline().is_some_and(|d| d <= Instant::now()) {
+            receiver.receive_all(reorder.expire());
+        }
+        tokio::time::sleep_until(start + EVENT_INTERVAL * (seq + 1)).await;
+    }
+
+    // wait for delayed packets and skipped events
+    loop {
+        let next = match (link.next_due(), reorder.deadline()) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(t), None) | (None, Some(t)) => t,
+            (None, None) => break,
+        };
+        tokio::time::sleep_until(next).await;
+        for (seq, event) in link.due() {
+            receiver.receive_all(reorder.push(seq, event));
+        }
+        if reorder.deadline().is_some_and(|d| d <= Instant::now()) {
+            receiver.receive_all(reorder.expire());
+        }
+    }
+
+    report(&options, &link, &receiver);
+    Ok(())
+}
+
+fn report(options: &LossTestOptions, link: &LossyLink, receiver: &Receiver) {
+    let percent = |n: u32| 100.0 * n as f64 / options.events.max(1) as f64;
+    let arrived = options.events - link.dropped;
+    println!(
+        "sent {} events, dropped {} ({:.1}%, {:?}), delayed {} ({:.1}%)",
+        options.events,
+        link.dropped,
+        percent(link.dropped),
+        options.pattern,
+        link.delayed,
+        percent(link.delayed),
+    );
+    println!(
+        "delivered {} events, skipped {}, {} arrived too late and were discarded",
+        receiver.delivered,
+        receiver.skipped,
+        arrived - receiver.delivered,
+    );
+    println!(
+        "stuck keys / buttons (release lost): {}",
+        receiver.stuck + receiver.held.len() as u32
+    );
+    println!("missed keys / buttons (press lost): {}", receiver.missed);
+    println!("events delivered out of order: {}", receiver.reordered);
+}
+
+/// the event sent with sequence number `seq`: a key press, motion, the key
+/// release, a click with motion in between and the button release.
+/// The time of every event is its sequence number.
+fn scripted_event(seq: u32) -> ProtoEvent {
+    let iteration = seq / SCRIPT_LEN;
+    let time = seq;
+    // KEY_1 .. KEY_0
+    let key = 2 + iteration % 10;
+    let event = match seq % SCRIPT_LEN {
+        0 => Event::Keyboard(KeyboardEvent::Key {
+            time,
+            key,
+            state: 1,
+        }),
+        7 => Event::Keyboard(KeyboardEvent::Key {
+            time,
+            key,
+            state: 0,
+        }),
+        8 => Event::Pointer(PointerEvent::Button {
+            time,
+            button: BTN_LEFT,
+            state: 1,
+        }),
+        13 => Event::Pointer(PointerEvent::Button {
+            time,
+            button: BTN_LEFT,
+            state: 0,
+        }),
+        _ => Event::Pointer(PointerEvent::Motion {
+            time,
+            dx: 1.0,
+            dy: 0.0,
+        }),
+    };
+    ProtoEvent::Input(event)
+}
+
+/// drops and delays packets, packets are encoded and decoded like on the wire
+struct LossyLink {
+    options: LossTestOptions,
+    rng: XorShift,
+    /// remaining packets of the current burst
+    burst: u32,
+    /// packets in flight and the time they arrive at
+    in_flight: Vec<(Instant, Vec<u8>)>,
+    dropped: u32,
+    delayed: u32,
+}
+
+impl LossyLink {
+    fn new(options: LossTestOptions) -> Self {
+        Self {
+            options,
+            rng: XorShift::from_time(),
+            burst: 0,
+            in_flight: vec![],
+            dropped: 0,
+            delayed: 0,
+        }
+    }
+
+    fn send(&mut self, seq: u32, event: ProtoEvent) {
+        if self.drop_next() {
+            self.dropped += 1;
+            return;
+        }
+        let mut arrival = Instant::now();
+        if self.rng.chance(self.options.reorder_percent / 100.0) {
+            self.delayed += 1;
+            let delay = 1 + self.rng.next() % MAX_REORDER_DELAY_MS;
+            arrival += Duration::from_millis(delay);
+        }
+        let (buf, len) = lan_mouse_proto::encode_packet(seq, event);
+        self.in_flight.push((arrival, buf[..len].to_vec()));
+    }
+
+    fn drop_next(&mut self) -> bool {
+        let p = self.options.drop_percent / 100.0;
+        match self.options.pattern {
+            LossPattern::Random => self.rng.chance(p),
+            LossPattern::Burst => {
+                if self.burst > 0 {
+                    self.burst -= 1;
+                    return true;
+                }
+                // chance to start a burst, such that `p` of all packets are dropped
+                let length = self.options.burst_length as f64;
+                let start = p / (length * (1.0 - p) + p);
+                if self.rng.chance(start) {
+                    self.burst = self.options.burst_length - 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// decoded packets that have arrived, in the order they arrived
+    fn due(&mut self) -> Vec<(u32, ProtoEvent)> {
+        let now = Instant::now();
+        let mut due = vec![];
+        self.in_flight.retain(|(arrival, buf)| {
+            if *arrival > now {
+                return true;
+            }
+            due.push((*arrival, buf.clone()));
+            false
+        });
+        due.sort_by_key(|(arrival, _)| *arrival);
+        due.into_iter()
+            .filter_map(|(_, buf)| match lan_mouse_proto::decode_packet(&buf) {
+                Ok(packet) => Some(packet),
+                Err(e) => {
+                    log::warn!("malformed packet: {e}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.in_flight.iter().map(|(arrival, _)| *arrival).min()
+    }
+}
+
+/// tracks the state the receiving device would end up in
+#[derive(Default)]
+struct Receiver {
+    /// pressed keys and buttons
+    held: HashSet<u32>,
+    /// time (= sequence number) of the last delivered event
+    last: Option<u32>,
+    delivered: u32,
+    skipped: u32,
+    reordered: u32,
+    /// presses of a key / button that was still held, because its release was lost
+    stuck: u32,
+    /// releases of a key / button that was not held, because its press was lost
+    missed: u32,
+}
+
+impl Receiver {
+    fn receive_all(&mut self, events: Vec<ProtoEvent>) {
+        for event in events {
+            self.receive(event);
+        }
+    }
+
+    fn receive(&mut self, event: ProtoEvent) {
+        let ProtoEvent::Input(event) = event else {
+            return;
+        };
+        let (time, pressed) = match event {
+            Event::Keyboard(KeyboardEvent::Key { time, key, state }) => {
+                (time, Some((key, state != 0)))
+            }
+            Event::Pointer(PointerEvent::Button {
+                time,
+                button,
+                state,
+            }) => (time, Some((button, state != 0))),
+            Event::Pointer(PointerEvent::Motion { time, .. }) => (time, None),
+            _ => return,
+        };
+        self.delivered += 1;
+        match self.last {
+            Some(last) if time <= last => self.reordered += 1,
+            Some(last) => self.skipped += time - last - 1,
+            None => self.skipped += time,
+        }
+        self.last = Some(self.last.map_or(time, |last| last.max(time)));
+        match pressed {
+            Some((code, true)) if !self.held.insert(code) => self.stuck += 1,
+            Some((code, false)) if !self.held.remove(&code) => self.missed += 1,
+            _ => {}
+        }
+    }
+}
+
+/// xorshift64, good enough to decide which packets to drop
+struct XorShift(u64);
+
+impl XorShift {
+    fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// true with probability `p`
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
@@ -3,8 +3,11 @@ use input_capture::InputCaptureError;
 use input_emulation::InputEmulationError;
 use lan_mouse::{
     capture_test,
-    config::{Config, ConfigError, Frontend},
+    config::{Command as LanMouseCommand, Config, ConfigError, ConfigToml, Frontend},
+    doctor::{self, DoctorError},
     emulation_test,
+    loss_test::{self, LossTestError, LossTestOptions},
+    peers::{self, PeersError},
     service::{Service, ServiceError},
 };
 use lan_mouse_ipc::{IpcError, IpcListenerCreationError};
@@ -30,6 +33,12 @@ enum LanMouseError {
     Capture(#[from] InputCaptureError),
     #[error(transparent)]
     Emulation(#[from] InputEmulationError),
+    #[error(transparent)]
+    Doctor(#[from] DoctorError),
+    #[error(transparent)]
+    Peers(#[from] PeersError),
+    #[error(transparent)]
+    LossTest(#[from] LossTestError),
 }
 
 fn main() {
@@ -46,7 +55,29 @@ fn main() {
 fn run() -> Result<(), LanMouseError> {
     // parse config file + cli args
     let config = Config::new()?;
-    if config.test_capture {
+    if let Some(LanMouseCommand::Init { force }) = config.command {
+        ConfigToml::write_starter(&config.config_file, force)?;
+        log::info!("wrote config to {:?}", config.config_file);
+    } else if let Some(LanMouseCommand::Doctor) = config.command {
+        run_async(doctor::run())?;
+    } else if let Some(LanMouseCommand::Peers) = config.command {
+        run_async(peers::run(config))?;
+    } else if let Some(LanMouseCommand::LossTest {
+        drop_percent,
+        pattern,
+        burst_length,
+        reorder_percent,
+        events,
+    }) = config.command
+    {
+        run_async(loss_test::run(LossTestOptions {
+            drop_percent,
+            pattern,
+            burst_length,
+            reorder_percent,
+            events,
+        }))?;
+    } else if config.test_capture {
         run_async(capture_test::run(config))?;
     } else if config.test_emulation {
         run_async(emulation_test::run(config))?;
@@ -0,0 +1,70 @@
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// file every sent and received packet is written to, if enabled
+static PACKET_LOG: OnceLock<Mutex<LineWriter<File>>> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
+    Sent,
+    Received,
+}
+
+/// log every packet to `path`, one line per packet:
+/// `<unix time> <sent|recv> <peer> <hex bytes> <decoded event>`
+pub(crate) fn enable(path: &Path) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    log::warn!(
+        "logging all packets to {}: this file contains every forwarded keystroke, including passwords!",
+        path.display()
+    );
+    if PACKET_LOG.set(Mutex::new(LineWriter::new(file))).is_err() {
+        log::warn!("packet log already enabled");
+    }
+    Ok(())
+}
+
+pub(crate) fn log(direction: Direction, peer: Option<SocketAddr>, packet: &[u8]) {
+    let Some(packet_log) = PACKET_LOG.get() else {
+        return;
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let direction = match direction {
+        Direction::Sent => "sent",
+        Direction::Received => "recv",
+    };
+    let peer = peer.map_or("-".to_string(), |a| a.to_string());
+    let mut hex = String::with_capacity(packet.len() * 2);
+    for b in packet {
+        let _ = write!(hex, "{b:02x}");
+    }
+    let decoded = match lan_mouse_proto::decode_packet(packet) {
+        Ok((seq, event)) => format!("#{seq} {event}"),
+        Err(e) => format!("malformed: {e}"),
+    };
+    let line = format!(
+        "{}.{:06} {direction} {peer} {hex} {decoded}",
+        time.as_secs(),
+        time.subsec_micros()
+    );
+    let mut file = packet_log.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = writeln!(file, "{line}") {
+        log::warn!("failed to write packet log: {e}");
+    }
+}
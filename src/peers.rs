@@ -0,0 +1,133 @@
+use std::{
+    net::SocketAddr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use hickory_resolver::{error::ResolveError, TokioAsyncResolver};
+use lan_mouse_proto::{ProtoEvent, ProtocolError};
+use thiserror::Error;
+
+use crate::{
+    config::{Config, ConfigClient},
+    connect::{self, LanMouseConnectionError},
+    crypto,
+    sequence::SequencedConn,
+};
+
+/// time to wait for a pong
+const PONG_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+pub enum PeersError {
+    #[error(transparent)]
+    Crypto(#[from] crypto::Error),
+    #[error(transparent)]
+    Resolve(#[from] ResolveError),
+    #[error("{0} of {1} clients not reachable")]
+    Unreachable(usize, usize),
+    #[error("no clients configured")]
+    NoClients,
+}
+
+#[derive(Debug, Error)]
+enum PingError {
+    #[error("no address (hostname did not resolve and no ips configured)")]
+    NoAddress,
+    #[error("could not connect: {0}")]
+    Connect(#[from] LanMouseConnectionError),
+    #[error("connection closed: {0}")]
+    Closed(#[from] webrtc_util::Error),
+    #[error("no response")]
+    Timeout,
+    #[error("{0}")]
+    Version(ProtocolError),
+}
+
+/// connect to every configured client, send a ping and report the round trip time
+pub async fn run(config: Config) -> Result<(), PeersError> {
+    let clients = config.get_clients();
+    if clients.is_empty() {
+        return Err(PeersError::NoClients);
+    }
+    let cert = crypto::load_or_generate_key_and_cert(&config.cert_path)?;
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+    let socket_options = Rc::new(config.socket_options.clone());
+
+    let mut unreachable = 0;
+    for client in clients.iter() {
+        let name = client
+            .hostname
+            .clone()
+            .or(client.ips.iter().next().map(|ip| ip.to_string()))
+            .unwrap_or_default();
+        let addrs = resolve(&resolver, client).await;
+        let result = async {
+            if addrs.is_empty() {
+                return Err(PingError::NoAddress);
+            }
+            let (conn, addr) = connect::connect_any(
+                &addrs,
+                client.source_ip,
+                cert.clone(),
+                socket_options.clone(),
+            )
+            .await?;
+            let conn = SequencedConn::new(conn);
+            let rtt = ping(&conn).await;
+            let _ = conn.close().await;
+            rtt.map(|(rtt, alive)| (addr, rtt, alive))
+        }
+        .await;
+        match result {
+            Ok((addr, rtt, alive)) => {
+                let emulation = if alive { "" } else { ", emulation disabled" };
+                println!(
+                    "{name} ({}) @ {addr}: ok, rtt {rtt:?}{emulation}",
+                    client.pos
+                );
+            }
+            Err(e) => {
+                unreachable += 1;
+                println!("{name} ({}): unreachable: {e}", client.pos);
+            }
+        }
+    }
+
+    if unreachable > 0 {
+        return Err(PeersError::Unreachable(unreachable, clients.len()));
+    }
+    Ok(())
+}
+
+/// configured and resolved addresses of a client
+async fn resolve(resolver: &TokioAsyncResolver, client: &ConfigClient) -> Vec<SocketAddr> {
+    let mut ips = client.ips.clone();
+    if let Some(hostname) = client.hostname.as_ref() {
+        match resolver.lookup_ip(hostname).await {
+            Ok(resolved) => ips.extend(resolved.iter()),
+            Err(e) => log::warn!("could not resolve {hostname}: {e}"),
+        }
+    }
+    ips.into_iter()
+        .map(|ip| SocketAddr::new(ip, client.port))
+        .collect()
+}
+
+/// round trip time of a ping and whether emulation is enabled on the client
+async fn ping(conn: &SequencedConn) -> Result<(Duration, bool), PingError> {
+    let sent = Instant::now();
+    conn.send(ProtoEvent::Ping).await?;
+    let pong = async {
+        loop {
+            match conn.recv().await? {
+                Ok((_, ProtoEvent::Pong(alive))) => return Ok((sent.elapsed(), alive)),
+                Err(e @ ProtocolError::UnsupportedVersion(_)) => return Err(PingError::Version(e)),
+                _ => {}
+            }
+        }
+    };
+    tokio::time::timeout(PONG_TIMEOUT, pong)
+        .await
+        .map_err(|_| PingError::Timeout)?
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    io,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use input_event::{Event, KeyboardEvent, PointerEvent};
+use lan_mouse_proto::{ProtoEvent, ProtocolError, MAX_PACKET_SIZE};
+use tokio::time::Instant;
+use webrtc_util::Conn;
+
+use crate::packet_log::{self, Direction};
+
+/// number of events that may be buffered while waiting for a missing one
+const REORDER_WINDOW: usize = 32;
+
+/// time to wait for a missing event before skipping it
+const REORDER_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// event encoded with its sequence number
+#[derive(Clone)]
+pub(crate) struct Packet {
+    seq: u32,
+    buf: [u8; MAX_PACKET_SIZE],
+    len: usize,
+}
+
+/// connection that tags every sent event with a sequence number
+pub(crate) struct SequencedConn {
+    conn: Arc<dyn Conn + Send + Sync>,
+    next_seq: AtomicU32,
+}
+
+impl SequencedConn {
+    pub(crate) fn new(conn: Arc<dyn Conn + Send + Sync>) -> Self {
+        Self {
+            conn,
+            next_seq: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) async fn send(&self, event: ProtoEvent) -> Result<usize, webrtc_util::Error> {
+        let packet = self.encode(event);
+        self.send_packet(&packet).await
+    }
+
+    /// encode `event` with the next sequence number
+    pub(crate) fn encode(&self, event: ProtoEvent) -> Packet {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let (buf, len) = lan_mouse_proto::encode_packet(seq, event);
+        Packet { seq, buf, len }
+    }
+
+    /// hand back the sequence number of a packet that is not sent,
+    /// so the receiver does not wait for it. Only possible if no later
+    /// packet has been encoded yet.
+    pub(crate) fn discard(&self, packet: Packet) {
+        let next = packet.seq.wrapping_add(1);
+        let _ =
+            self.next_seq
+                .compare_exchange(next, packet.seq, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// send an encoded packet, it may be sent again if sending failed
+    pub(crate) async fn send_packet(&self, packet: &Packet) -> Result<usize, webrtc_util::Error> {
+        let buf = &packet.buf[..packet.len];
+        packet_log::log(Direction::Sent, self.conn.remote_addr(), buf);
+        self.conn.send(buf).await
+    }
+
+    /// receive the next packet, `Ok(Err(_))` if the packet is malformed
+    pub(crate) async fn recv(
+        &self,
+    ) -> Result<Result<(u32, ProtoEvent), ProtocolError>, webrtc_util::Error> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let len = self.conn.recv(&mut buf).await?;
+        packet_log::log(Direction::Received, self.conn.remote_addr(), &buf[..len]);
+        Ok(lan_mouse_proto::decode_packet(&buf[..len]))
+    }
+
+    pub(crate) async fn close(&self) -> Result<(), webrtc_util::Error> {
+        self.conn.close().await
+    }
+
+    pub(crate) fn inner(&self) -> &Arc<dyn Conn + Send + Sync> {
+        &self.conn
+    }
+}
+
+/// whether sending failed because the socket buffer is full
+/// (`EWOULDBLOCK` / `ENOBUFS`), the connection is still usable in that case
+pub(crate) fn is_congested(e: &webrtc_util::Error) -> bool {
+    let Some(e) = io_error(e) else {
+        return false;
+    };
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::ENOBUFS) {
+        return true;
+    }
+    e.kind() == io::ErrorKind::WouldBlock
+}
+
+/// the io error a send error was caused by, looking through the dtls layer
+fn io_error(e: &webrtc_util::Error) -> Option<&io::Error> {
+    let mut source: Option<&(dyn Error + 'static)> = match e {
+        webrtc_util::Error::Io(e) => return Some(&e.0),
+        // errors of the dtls connection are boxed
+        webrtc_util::Error::Std(e) => Some(e.0.as_ref()),
+        _ => None,
+    };
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            return Some(e);
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// restores the order of received events.
+/// Events arriving out of order are held back until the missing
+/// events arrive or [`REORDER_TIMEOUT`] has passed. Events arriving
+/// after they have been skipped are dropped, except for releases and
+/// control events, which are delivered late instead of leaving keys
+/// pressed on the receiver.
+#[derive(Default)]
+pub(crate) struct Reorder {
+    /// sequence number of the next event to deliver
+    next: Option<u32>,
+    /// buffered events, index `i` holds the event with sequence number `next + i`
+    pending: VecDeque<Option<ProtoEvent>>,
+    /// time at which the missing event is skipped
+    deadline: Option<Instant>,
+}
+
+impl Reorder {
+    /// insert a received event, returns all events that are now in order
+    pub(crate) fn push(&mut self, seq: u32, event: ProtoEvent) -> Vec<ProtoEvent> {
+        let next = *self.next.get_or_insert(seq);
+        let offset = seq.wrapping_sub(next) as i32;
+        let mut ready = vec![];
+        if offset < 0 {
+            if delivered_late(&event) {
+                log::debug!("delivering late event {seq} (expected {next}): {event}");
+                ready.push(event);
+            } else {
+                log::debug!("dropping late event {seq} (expected {next}): {event}");
+            }
+            return ready;
+        }
+        let offset = offset as usize;
+        if offset >= REORDER_WINDOW {
+            // too far ahead to wait for the missing events
+            log::debug!("skipping events {next}..{seq}");
+            ready.extend(self.pending.drain(..).flatten());
+            ready.push(event);
+            self.next = Some(seq.wrapping_add(1));
+            self.deadline = None;
+            return ready;
+        }
+        if self.pending.len() <= offset {
+            self.pending.resize(offset + 1, None);
+        }
+        self.pending[offset] = Some(event);
+        self.drain_ready(&mut ready);
+        ready
+    }
+
+    /// time at which [`Reorder::expire`] needs to be called
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// skip the missing event, returns all events that are now in order
+    pub(crate) fn expire(&mut self) -> Vec<ProtoEvent> {
+        let mut ready = vec![];
+        while let Some(None) = self.pending.front() {
+            self.pending.pop_front();
+            self.advance();
+        }
+        self.deadline = None;
+        self.drain_ready(&mut ready);
+        ready
+    }
+
+    fn drain_ready(&mut self, ready: &mut Vec<ProtoEvent>) {
+        while let Some(Some(_)) = self.pending.front() {
+            ready.extend(self.pending.pop_front().flatten());
+            self.advance();
+        }
+        if self.pending.is_empty() {
+            self.deadline = None;
+        } else if self.deadline.is_none() {
+            self.deadline = Some(Instant::now() + REORDER_TIMEOUT);
+        }
+    }
+
+    fn advance(&mut self) {
+        self.next = self.next.map(|n| n.wrapping_add(1));
+    }
+}
+
+/// whether an event is still delivered after it has been skipped.
+/// Motion, scrolling, presses and modifiers are outdated by then (a late
+/// press could follow its own release), a release is always safe to apply
+fn delivered_late(event: &ProtoEvent) -> bool {
+    match event {
+        ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key { state, .. })) => *state == 0,
+        ProtoEvent::Input(Event::Pointer(PointerEvent::Button { state, .. })) => *state == 0,
+        ProtoEvent::Input(_) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: u32, state: u8) -> ProtoEvent {
+        ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key,
+            state,
+        }))
+    }
+
+    fn button(state: u32) -> ProtoEvent {
+        ProtoEvent::Input(Event::Pointer(PointerEvent::Button {
+            time: 0,
+            button: input_event::BTN_LEFT,
+            state,
+        }))
+    }
+
+    fn motion() -> ProtoEvent {
+        ProtoEvent::Input(Event::Pointer(PointerEvent::Motion {
+            time: 0,
+            dx: 1.,
+            dy: 0.,
+        }))
+    }
+
+    #[test]
+    fn in_order() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(10, key(30, 1)), vec![key(30, 1)]);
+        assert_eq!(reorder.push(11, key(30, 0)), vec![key(30, 0)]);
+        assert!(reorder.deadline().is_none());
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(0, key(30, 1)), vec![key(30, 1)]);
+        // release arrives before the press of the button
+        assert_eq!(reorder.push(2, button(0)), vec![]);
+        assert_eq!(reorder.push(3, key(30, 0)), vec![]);
+        assert!(reorder.deadline().is_some());
+        assert_eq!(
+            reorder.push(1, button(1)),
+            vec![button(1), button(0), key(30, 0)]
+        );
+        assert!(reorder.deadline().is_none());
+    }
+
+    #[test]
+    fn skipped() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(0, key(30, 1)), vec![key(30, 1)]);
+        assert_eq!(reorder.push(2, key(30, 0)), vec![]);
+        assert_eq!(reorder.expire(), vec![key(30, 0)]);
+        assert!(reorder.deadline().is_none());
+        // the skipped event is not delivered anymore
+        assert_eq!(reorder.push(1, motion()), vec![]);
+        assert_eq!(reorder.push(3, motion()), vec![motion()]);
+    }
+
+    #[test]
+    fn late_release() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(0, key(30, 1)), vec![key(30, 1)]);
+        assert_eq!(reorder.push(1, button(1)), vec![button(1)]);
+        assert_eq!(reorder.push(4, motion()), vec![]);
+        assert_eq!(reorder.expire(), vec![motion()]);
+        // releases skipped by the timeout still arrive
+        assert_eq!(reorder.push(3, button(0)), vec![button(0)]);
+        assert_eq!(reorder.push(2, key(30, 0)), vec![key(30, 0)]);
+    }
+
+    #[test]
+    fn late_press() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(0, motion()), vec![motion()]);
+        assert_eq!(reorder.push(3, key(30, 0)), vec![]);
+        assert_eq!(reorder.expire(), vec![key(30, 0)]);
+        // the release was already delivered, so the press must not be
+        assert_eq!(reorder.push(2, key(30, 1)), vec![]);
+        assert_eq!(reorder.push(1, motion()), vec![]);
+    }
+
+    #[test]
+    fn window_overflow() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(0, key(30, 1)), vec![key(30, 1)]);
+        assert_eq!(reorder.push(2, motion()), vec![]);
+        let far = 1 + REORDER_WINDOW as u32;
+        assert_eq!(reorder.push(far, key(30, 0)), vec![motion(), key(30, 0)]);
+        assert!(reorder.deadline().is_none());
+        assert_eq!(reorder.push(1, key(30, 0)), vec![key(30, 0)]);
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut reorder = Reorder::default();
+        assert_eq!(reorder.push(u32::MAX, key(30, 1)), vec![key(30, 1)]);
+        assert_eq!(reorder.push(1, motion()), vec![]);
+        assert_eq!(reorder.push(0, key(30, 0)), vec![key(30, 0), motion()]);
+    }
+}
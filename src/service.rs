@@ -2,11 +2,13 @@ use crate::{
     capture::{Capture, CaptureType, ICaptureEvent},
     client::ClientManager,
     config::Config,
-    connect::LanMouseConnection,
+    connect::{ConnectionEvent, LanMouseConnection},
     crypto,
     dns::{DnsEvent, DnsResolver},
     emulation::{Emulation, EmulationEvent},
     listen::{LanMouseListener, ListenerCreationError},
+    packet_log,
+    transform::{EventTransformer, KeyMacros, KeyRemap},
 };
 use futures::StreamExt;
 use hickory_resolver::error::ResolveError;
@@ -14,6 +16,7 @@ use lan_mouse_ipc::{
     AsyncFrontendListener, ClientConfig, ClientHandle, ClientState, FrontendEvent, FrontendRequest,
     IpcError, IpcListenerCreationError, Position, Status,
 };
+use local_channel::mpsc::{channel, Receiver};
 use log;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
@@ -43,6 +46,8 @@ pub struct Service {
     capture: Capture,
     /// input emulation
     emulation: Emulation,
+    /// state changes of outgoing connections
+    connection_events: Receiver<ConnectionEvent>,
     /// dns resolver
     resolver: DnsResolver,
     /// frontend listener
@@ -63,6 +68,10 @@ pub struct Service {
     capture_status: Status,
     /// status of input emulation (enabled / disabled)
     emulation_status: Status,
+    /// client currently receiving input (`None` => local)
+    cursor_owner: Option<ClientHandle>,
+    /// forwarding of captured input is paused
+    paused: bool,
     /// keep track of registered connections to avoid duplicate barriers
     incoming_conns: HashSet<SocketAddr>,
     /// map from capture handle to connection info
@@ -79,6 +88,15 @@ struct Incoming {
 
 impl Service {
     pub async fn new(config: Config) -> Result<Self, ServiceError> {
+        Self::with_transformers(config, vec![]).await
+    }
+
+    /// create the service with additional transformers,
+    /// applied in order to captured input after the key remap
+    pub async fn with_transformers(
+        config: Config,
+        transformers: Vec<Box<dyn EventTransformer>>,
+    ) -> Result<Self, ServiceError> {
         let client_manager = ClientManager::default();
         for client in config.get_clients() {
             let config = ClientConfig {
@@ -87,6 +105,15 @@ impl Service {
                 port: client.port,
                 pos: client.pos,
                 cmd: client.enter_hook,
+                edge_span: client.edge_span,
+                invert_x: client.invert_x,
+                invert_y: client.invert_y,
+                swap_axes: client.swap_axes,
+                invert_scroll: client.invert_scroll,
+                forward: client.forward,
+                suppress_in_fullscreen: client.suppress_in_fullscreen,
+                send_delay_ms: client.send_delay_ms,
+                source_ip: client.source_ip,
             };
             let state = ClientState {
                 active: client.active,
@@ -98,6 +125,10 @@ impl Service {
             client_manager.set_state(handle, state);
         }
 
+        if let Some(path) = &config.packet_log {
+            packet_log::enable(path)?;
+        }
+
         // load certificate
         let cert = crypto::load_or_generate_key_and_cert(&config.cert_path)?;
         let public_key_fingerprint = crypto::certificate_fingerprint(&cert);
@@ -109,13 +140,26 @@ impl Service {
         // listener + connection
         let listener =
             LanMouseListener::new(config.port, cert.clone(), authorized_keys.clone()).await?;
-        let conn = LanMouseConnection::new(cert.clone(), client_manager.clone());
+        let (connection_event_tx, connection_events) = channel();
+        let conn = LanMouseConnection::new(
+            cert.clone(),
+            config.socket_options.clone(),
+            client_manager.clone(),
+            connection_event_tx,
+        );
 
         // input capture + emulation
-        let capture_backend = config.capture_backend.map(|b| b.into());
-        let capture = Capture::new(capture_backend, conn, config.release_bind.clone());
+        let mut pipeline: Vec<Box<dyn EventTransformer>> = vec![];
+        if !config.key_remap.is_empty() {
+            pipeline.push(Box::new(KeyRemap::new(&config.key_remap)));
+        }
+        if !config.macros.is_empty() {
+            pipeline.push(Box::new(KeyMacros::new(&config.macros)));
+        }
+        pipeline.extend(transformers);
+        let capture = Capture::new(&config, conn, client_manager.clone(), pipeline);
         let emulation_backend = config.emulation_backend.map(|b| b.into());
-        let emulation = Emulation::new(emulation_backend, listener);
+        let emulation = Emulation::new(emulation_backend, config.motion_prediction, listener);
 
         // create dns resolver
         let resolver = DnsResolver::new()?;
@@ -124,6 +168,7 @@ impl Service {
         let service = Self {
             capture,
             emulation,
+            connection_events,
             frontend_listener,
             resolver,
             authorized_keys,
@@ -134,6 +179,8 @@ impl Service {
             pending_frontend_events: Default::default(),
             capture_status: Default::default(),
             emulation_status: Default::default(),
+            cursor_owner: None,
+            paused: false,
             incoming_conn_info: Default::default(),
             incoming_conns: Default::default(),
             next_trigger_handle: 0,
@@ -156,6 +203,7 @@ impl Service {
                 _ = self.frontend_event_pending.notified() => self.handle_frontend_pending().await,
                 event = self.emulation.event() => self.handle_emulation_event(event),
                 event = self.capture.event() => self.handle_capture_event(event),
+                event = self.connection_events.recv() => self.handle_connection_event(event.expect("channel closed")),
                 event = self.resolver.event() => self.handle_resolver_event(event),
                 r = signal::ctrl_c() => break r.expect("failed to wait for CTRL+C"),
             }
@@ -194,6 +242,7 @@ impl Service {
             FrontendRequest::ResolveDns(handle) => self.resolve(handle),
             FrontendRequest::Sync => self.sync_frontend(),
             FrontendRequest::RemoveAuthorizedKey(key) => self.remove_authorized_key(key),
+            FrontendRequest::DebugState => self.capture.request_debug_state(),
         }
     }
 
@@ -203,6 +252,20 @@ impl Service {
         }
     }
 
+    fn handle_connection_event(&mut self, event: ConnectionEvent) {
+        let event = match event {
+            ConnectionEvent::Connected(handle, addr) => {
+                FrontendEvent::ClientConnected(handle, addr)
+            }
+            ConnectionEvent::Failed(handle, e) => FrontendEvent::ClientConnectionFailed(handle, e),
+            ConnectionEvent::Disconnected(handle, reason) => {
+                FrontendEvent::ClientDisconnected(handle, reason)
+            }
+            ConnectionEvent::Rtt(handle, rtt) => FrontendEvent::ClientRtt(handle, rtt),
+        };
+        self.notify_frontend(event);
+    }
+
     fn handle_emulation_event(&mut self, event: EmulationEvent) {
         match event {
             EmulationEvent::Connected {
@@ -264,6 +327,25 @@ impl Service {
                 log::info!("entering client {handle} ...");
                 self.spawn_hook_command(handle);
             }
+            ICaptureEvent::CursorOwner(owner) => {
+                match owner {
+                    Some(handle) => log::info!("cursor owner: client {handle}"),
+                    None => log::info!("cursor owner: local"),
+                }
+                self.cursor_owner = owner;
+                self.notify_frontend(FrontendEvent::CursorOwner(owner));
+            }
+            ICaptureEvent::Paused(paused) => {
+                match paused {
+                    true => log::info!("input forwarding paused"),
+                    false => log::info!("input forwarding resumed"),
+                }
+                self.paused = paused;
+                self.notify_frontend(FrontendEvent::Paused(paused));
+            }
+            ICaptureEvent::DebugState(state) => {
+                self.notify_frontend(FrontendEvent::DebugState(state));
+            }
         }
     }
 
@@ -297,6 +379,8 @@ impl Service {
         self.notify_frontend(FrontendEvent::EmulationStatus(self.emulation_status));
         self.notify_frontend(FrontendEvent::CaptureStatus(self.capture_status));
         self.notify_frontend(FrontendEvent::PortChanged(self.port, None));
+        self.notify_frontend(FrontendEvent::CursorOwner(self.cursor_owner));
+        self.notify_frontend(FrontendEvent::Paused(self.paused));
         self.notify_frontend(FrontendEvent::PublicKeyFingerprint(
             self.public_key_fingerprint.clone(),
         ));
@@ -309,7 +393,8 @@ impl Service {
     fn add_incoming(&mut self, addr: SocketAddr, pos: Position, fingerprint: String) {
         let handle = Self::ENTER_HANDLE_BEGIN + self.next_trigger_handle;
         self.next_trigger_handle += 1;
-        self.capture.create(handle, pos, CaptureType::EnterOnly);
+        self.capture
+            .create(handle, pos, Default::default(), CaptureType::EnterOnly);
         self.incoming_conns.insert(addr);
         self.incoming_conn_info.insert(
             handle,
@@ -424,7 +509,8 @@ impl Service {
         /* activate the client */
         if self.client_manager.activate_client(handle) {
             /* notify capture and frontends */
-            self.capture.create(handle, pos, CaptureType::Default);
+            let span = self.client_manager.get_edge_span(handle);
+            self.capture.create(handle, pos, span, CaptureType::Default);
             self.notify_frontend(FrontendEvent::Changed(handle));
             log::info!("activated client {handle} ({pos})");
         }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use input_event::{scancode, Event, KeyboardEvent};
+
+/// modifies captured events before they are sent to a client
+pub trait EventTransformer {
+    /// transform a captured event into the events sent in its place,
+    /// an empty `Vec` drops the event
+    fn transform(&mut self, event: Event) -> Vec<Event>;
+}
+
+/// replaces keys according to a remap table
+pub struct KeyRemap {
+    remap: HashMap<u32, u32>,
+}
+
+impl KeyRemap {
+    pub fn new(remap: &HashMap<scancode::Linux, scancode::Linux>) -> Self {
+        let remap = remap
+            .iter()
+            .map(|(&from, &to)| (from as u32, to as u32))
+            .collect();
+        Self { remap }
+    }
+}
+
+impl EventTransformer for KeyRemap {
+    fn transform(&mut self, event: Event) -> Vec<Event> {
+        let event = match event {
+            Event::Keyboard(KeyboardEvent::Key { time, key, state }) => {
+                let key = self.remap.get(&key).copied().unwrap_or(key);
+                Event::Keyboard(KeyboardEvent::Key { time, key, state })
+            }
+            event => event,
+        };
+        vec![event]
+    }
+}
+
+/// expands a key into a sequence of key chords
+pub struct KeyMacros {
+    /// every chord is pressed in order and released in reverse order
+    macros: HashMap<u32, Vec<Vec<u32>>>,
+}
+
+impl KeyMacros {
+    pub fn new(macros: &HashMap<scancode::Linux, Vec<Vec<scancode::Linux>>>) -> Self {
+        let macros = macros
+            .iter()
+            .map(|(&key, chords)| {
+                let chords = chords
+                    .iter()
+                    .map(|chord| chord.iter().map(|&k| k as u32).collect())
+                    .collect();
+                (key as u32, chords)
+            })
+            .collect();
+        Self { macros }
+    }
+}
+
+impl EventTransformer for KeyMacros {
+    fn transform(&mut self, event: Event) -> Vec<Event> {
+        let Event::Keyboard(KeyboardEvent::Key { time, key, state }) = event else {
+            return vec![event];
+        };
+        let Some(chords) = self.macros.get(&key) else {
+            return vec![event];
+        };
+        // the sequence is sent on press, repeats and the release are dropped
+        if state != 1 {
+            return vec![];
+        }
+        let key = |key, state| Event::Keyboard(KeyboardEvent::Key { time, key, state });
+        chords
+            .iter()
+            .flat_map(|chord| {
+                let press = chord.iter().map(move |&k| key(k, 1));
+                let release = chord.iter().rev().map(move |&k| key(k, 0));
+                press.chain(release)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scancode::Linux::{KeyF13, KeyH, KeyLeftShift};
+
+    fn key(key: scancode::Linux, state: u8) -> Event {
+        Event::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key: key as u32,
+            state,
+        })
+    }
+
+    #[test]
+    fn macro_fires_once_per_press() {
+        let macros = HashMap::from([(KeyF13, vec![vec![KeyLeftShift, KeyH]])]);
+        let mut macros = KeyMacros::new(&macros);
+        assert_eq!(
+            macros.transform(key(KeyF13, 1)),
+            vec![
+                key(KeyLeftShift, 1),
+                key(KeyH, 1),
+                key(KeyH, 0),
+                key(KeyLeftShift, 0)
+            ]
+        );
+        // key repeat
+        assert_eq!(macros.transform(key(KeyF13, 2)), vec![]);
+        assert_eq!(macros.transform(key(KeyF13, 0)), vec![]);
+        assert_eq!(macros.transform(key(KeyH, 1)), vec![key(KeyH, 1)]);
+    }
+}